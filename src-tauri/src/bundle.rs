@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::models::{ConfigBundle, ImportConflict, ImportMode, ImportReport, Settings, VideoFilter, VideoModifier, WatchFolder};
+use crate::scratch::StagedFile;
+use crate::settings::{self, SETTINGS_VERSION};
+use crate::{ensure_config_files, get_config_path, get_filters, get_modifiers, FILTERS_FILE, MODIFIERS_FILE};
+
+/// Version of the bundle layout written by this build.
+pub const BUNDLE_VERSION: u32 = 1;
+
+const FILTERS_HEADER: &[&str] = &["short_name", "long_name", "priority", "code"];
+const MODIFIERS_HEADER: &[&str] = &["short_name", "long_name", "code"];
+
+/// Writes `rows` as a tab-separated table in the layout `ensure_config_files`
+/// creates, to be moved over `path` once the rest of the import is written.
+fn stage_table<T: Serialize>(path: PathBuf, header: &[&str], rows: &[T]) -> Result<StagedFile, String> {
+    StagedFile::new(path, |temp_path| {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(b'\t')
+            .has_headers(false)
+            .from_path(temp_path)
+            .map_err(|e| e.to_string())?;
+        // Written by hand so an empty table still gets its header
+        writer.write_record(header).map_err(|e| e.to_string())?;
+        for row in rows {
+            writer.serialize(row).map_err(|e| e.to_string())?;
+        }
+        writer.flush().map_err(|e| e.to_string())
+    })
+}
+
+fn check_unique<T>(items: &[T], name: impl Fn(&T) -> &str, kind: &str) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    match items.iter().map(&name).find(|n| !seen.insert(*n)) {
+        Some(duplicate) => Err(format!("The bundle contains the {} \"{}\" more than once", kind, duplicate)),
+        None => Ok(()),
+    }
+}
+
+/// Combines `local` and `incoming` entries, matched by name. New names are
+/// added either way; a name used locally for something different is a
+/// conflict, which keeps the local entry when merging and takes the
+/// incoming one when replacing (where local-only entries are dropped too).
+fn combine<T: Clone + PartialEq>(local: Vec<T>, incoming: Vec<T>, name: impl Fn(&T) -> &str, mode: ImportMode, kind: &str, added: &mut Vec<String>, conflicts: &mut Vec<ImportConflict>) -> Vec<T> {
+    for item in &incoming {
+        match local.iter().find(|l| name(l) == name(item)) {
+            None => added.push(name(item).to_string()),
+            Some(existing) if existing != item => conflicts.push(ImportConflict {
+                kind: kind.to_string(),
+                short_name: name(item).to_string(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    match mode {
+        ImportMode::Replace => incoming,
+        ImportMode::Merge => {
+            let mut combined = local;
+            for item in incoming {
+                if !combined.iter().any(|l| name(l) == name(&item)) {
+                    combined.push(item);
+                }
+            }
+            combined
+        }
+    }
+}
+
+/// Binary path to use after importing: an imported path is only taken if it
+/// exists on this machine, and when merging only where none is set locally.
+fn combine_path(local: &Option<String>, imported: &Option<String>, mode: ImportMode, tool: &str, warnings: &mut Vec<String>) -> Option<String> {
+    match imported {
+        Some(path) if !Path::new(path).is_file() => {
+            warnings.push(format!("The {} path {} doesn't exist on this machine, keeping the local one", tool, path));
+            local.clone()
+        }
+        Some(path) if mode == ImportMode::Replace || local.is_none() => Some(path.clone()),
+        None if mode == ImportMode::Replace => None,
+        _ => local.clone(),
+    }
+}
+
+/// A settings section that only exists once: the bundle's when replacing
+/// (reported if it differs), the local one when merging.
+fn combine_section<T: PartialEq>(local: T, imported: T, mode: ImportMode, section: &str, replaced: &mut Vec<String>) -> T {
+    match mode {
+        ImportMode::Replace => {
+            if local != imported {
+                replaced.push(section.to_string());
+            }
+            imported
+        }
+        ImportMode::Merge => local,
+    }
+}
+
+/// Writes the filter and modifier tables and the settings (with presets,
+/// resource limits, schedule and watch folders) into a single bundle at `path`.
+#[tauri::command]
+pub fn export_config_bundle(app: AppHandle, path: String) -> Result<(), String> {
+    let bundle = ConfigBundle {
+        version: BUNDLE_VERSION,
+        exported_at: chrono::Local::now().to_rfc3339(),
+        filters: get_filters(app.clone())?,
+        modifiers: get_modifiers(app.clone())?,
+        settings: serde_json::to_value(settings::load(&app)?).map_err(|e| e.to_string())?,
+    };
+    let content = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+    std::fs::write(path, content).map_err(|e| e.to_string())
+}
+
+/// Combines the local tables and `local` settings with the bundle's,
+/// recording what changes in `report`.
+fn combine_all(app: &AppHandle, local: Settings, filters: Vec<VideoFilter>, modifiers: Vec<VideoModifier>, imported: Settings, mode: ImportMode, report: &mut ImportReport) -> Result<(Settings, Vec<VideoFilter>, Vec<VideoModifier>), String> {
+    let filters = combine(get_filters(app.clone())?, filters, |f: &VideoFilter| &f.short_name, mode, "filter", &mut report.filters_added, &mut report.conflicts);
+    let modifiers = combine(get_modifiers(app.clone())?, modifiers, |m: &VideoModifier| &m.short_name, mode, "modifier", &mut report.modifiers_added, &mut report.conflicts);
+    let presets = combine(local.presets, imported.presets, |p| &p.name, mode, "preset", &mut report.presets_added, &mut report.conflicts);
+
+    let mut incoming_folders = imported.watch_folders;
+    incoming_folders.retain(|folder| {
+        let exists = Path::new(&folder.path).is_dir();
+        if !exists {
+            report.warnings.push(format!("The watch folder {} doesn't exist on this machine, skipping it", folder.path));
+        }
+        exists
+    });
+    let watch_folders = combine(local.watch_folders, incoming_folders, |w: &WatchFolder| &w.path, mode, "watch_folder", &mut report.watch_folders_added, &mut report.conflicts);
+
+    let mut ffmpeg = local.ffmpeg.clone();
+    ffmpeg.ffmpeg = combine_path(&local.ffmpeg.ffmpeg, &imported.ffmpeg.ffmpeg, mode, "ffmpeg", &mut report.warnings);
+    ffmpeg.ffprobe = combine_path(&local.ffmpeg.ffprobe, &imported.ffmpeg.ffprobe, mode, "ffprobe", &mut report.warnings);
+
+    let settings = Settings {
+        version: SETTINGS_VERSION,
+        defaults: combine_section(local.defaults, imported.defaults, mode, "defaults", &mut report.sections_replaced),
+        ffmpeg,
+        presets,
+        limits: combine_section(local.limits, imported.limits, mode, "limits", &mut report.sections_replaced),
+        schedule: combine_section(local.schedule, imported.schedule, mode, "schedule", &mut report.sections_replaced),
+        watch_folders,
+    };
+    Ok((settings, filters, modifiers))
+}
+
+/// Imports the bundle at `path`, merging it into or replacing the local
+/// configuration. Default options, resource limits and the schedule are
+/// kept when merging and taken from the bundle when replacing. With
+/// `dry_run`, only reports what would change. Every file is written in full
+/// before any of them is replaced, so a failed import changes nothing.
+#[tauri::command]
+pub async fn import_config_bundle(app: AppHandle, path: String, mode: ImportMode, dry_run: bool) -> Result<ImportReport, String> {
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let bundle: ConfigBundle = serde_json::from_str(&content).map_err(|e| format!("Invalid config bundle: {}", e))?;
+    if bundle.version > BUNDLE_VERSION {
+        return Err(format!("The bundle has version {}, but this build only understands up to {}", bundle.version, BUNDLE_VERSION));
+    }
+    let imported = settings::from_value(bundle.settings)?;
+    check_unique(&bundle.filters, |f| &f.short_name, "filter")?;
+    check_unique(&bundle.modifiers, |m| &m.short_name, "modifier")?;
+    check_unique(&imported.presets, |p| &p.name, "preset")?;
+    check_unique(&imported.watch_folders, |w| &w.path, "watch folder")?;
+
+    ensure_config_files(&app)?;
+    let mut report = ImportReport::default();
+    if dry_run {
+        combine_all(&app, settings::load(&app)?, bundle.filters, bundle.modifiers, imported, mode, &mut report)?;
+        return Ok(report);
+    }
+
+    // Read, combined and written under the settings lock, so nothing saved
+    // meanwhile is lost; the settings are validated before anything moves
+    settings::update_with(&app, |local| {
+        let (settings, filters, modifiers) = combine_all(&app, local, bundle.filters, bundle.modifiers, imported, mode, &mut report)?;
+        let tables = vec![
+            stage_table(get_config_path(&app, FILTERS_FILE)?, FILTERS_HEADER, &filters)?,
+            stage_table(get_config_path(&app, MODIFIERS_FILE)?, MODIFIERS_HEADER, &modifiers)?,
+        ];
+        Ok((settings, tables))
+    }).await?;
+    report.applied = true;
+    Ok(report)
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use sysinfo::Disks;
+
+use crate::models::{TagMode, VideoOptions};
+
+/// Output size assumed relative to the source when no bitrate is given.
+/// Re-encodes are usually smaller, but a low-bitrate source encoded at a
+/// high quality can grow, so don't count on savings.
+const SIZE_RATIO: f64 = 1.0;
+
+/// Share of the source assumed to be audio, which is copied as is.
+const AUDIO_SHARE: f64 = 0.1;
+
+/// Extra fraction added to every estimate for container overhead and
+/// bitrate overshoot.
+const SAFETY_MARGIN: f64 = 0.1;
+
+/// Free space left untouched on every filesystem so the system stays usable.
+const RESERVED_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Parses ffmpeg bitrates like `2500k`, `8M` or `1.5G` into bits per second.
+fn parse_bitrate(value: &str) -> Option<f64> {
+    let (number, multiplier) = match value.chars().last()? {
+        'k' | 'K' => (&value[..value.len() - 1], 1e3),
+        'm' | 'M' => (&value[..value.len() - 1], 1e6),
+        'g' | 'G' => (&value[..value.len() - 1], 1e9),
+        _ => (value, 1.0),
+    };
+    number.parse::<f64>().ok().map(|n| n * multiplier)
+}
+
+/// Video bitrate requested through the resolved modifier arguments (`-b:v`,
+/// falling back to `-maxrate`), in bits per second.
+fn requested_bitrate(modifier_args: &[String]) -> Option<f64> {
+    let value_of = |flags: &[&str]| modifier_args.windows(2)
+        .find(|pair| flags.contains(&pair[0].as_str()))
+        .and_then(|pair| parse_bitrate(&pair[1]));
+    value_of(&["-b:v", "-b"]).or_else(|| value_of(&["-maxrate"]))
+}
+
+/// Estimated size of the encoded output in bytes. With a target bitrate the
+/// video part is computed from it, plus `AUDIO_SHARE` of the source for the
+/// copied audio; otherwise a ratio of the source size is used.
+pub fn estimate_output_size(source_size: u64, duration_secs: f64, modifier_args: &[String]) -> u64 {
+    let estimate = match requested_bitrate(modifier_args) {
+        Some(bitrate) if duration_secs > 0.0 => bitrate * duration_secs / 8.0 + source_size as f64 * AUDIO_SHARE,
+        _ => source_size as f64 * SIZE_RATIO,
+    };
+    (estimate * (1.0 + SAFETY_MARGIN)) as u64
+}
+
+/// Closest existing ancestor of `path`, resolved, so it can be matched
+/// against mount points before the output directory has been created.
+fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find_map(|p| p.canonicalize().ok())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Mount point and available bytes of the filesystem holding `path`.
+fn filesystem_of(disks: &Disks, path: &Path) -> Option<(PathBuf, u64)> {
+    let path = existing_ancestor(path);
+    disks.list().iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| (disk.mount_point().to_path_buf(), disk.available_space()))
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Checks that the filesystems an encode of `input` touches have room for
+/// it before starting: the temp file in `temp_dir`, a copy into `output_dir`
+/// when it's a different filesystem (the move can't be a rename), and the
+/// remuxed original when tagging rewrites it. Filesystems whose free space
+/// can't be determined are not checked. `modifier_args` are the arguments
+/// the selected modifiers resolved to, as passed to ffmpeg.
+pub fn check_free_space(input: &Path, source_size: u64, duration_secs: f64, options: &VideoOptions, modifier_args: &[String], temp_dir: &Path, output_dir: &Path) -> Result<(), String> {
+    let disks = Disks::new_with_refreshed_list();
+    let output_size = estimate_output_size(source_size, duration_secs, modifier_args);
+
+    // Bytes needed per filesystem, keyed by mount point
+    let mut required: BTreeMap<PathBuf, (u64, u64)> = BTreeMap::new();
+    let mut require = |path: &Path, bytes: u64| {
+        if let Some((mount, available)) = filesystem_of(&disks, path) {
+            required.entry(mount).or_insert((0, available)).0 += bytes;
+        }
+    };
+
+    // Segments and the file they are joined into exist side by side
+    require(temp_dir, if options.segmented { output_size * 2 } else { output_size });
+    let same_filesystem = filesystem_of(&disks, temp_dir).map(|f| f.0) == filesystem_of(&disks, output_dir).map(|f| f.0);
+    if !same_filesystem {
+        require(output_dir, output_size);
+    }
+    if options.tag_original && options.tag_mode == TagMode::File && !options.replace_original {
+        if let Some(parent) = input.parent() {
+            require(parent, source_size);
+        }
+    }
+
+    let shortfalls: Vec<String> = required.iter()
+        .filter(|(_, (needed, available))| needed + RESERVED_BYTES > *available)
+        .map(|(mount, (needed, available))| format!(
+            "{} needs about {} but only {} is available",
+            mount.display(), format_bytes(needed + RESERVED_BYTES), format_bytes(*available)
+        ))
+        .collect();
+
+    if shortfalls.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Not enough disk space to process {}: {}", input.display(), shortfalls.join("; ")))
+    }
+}
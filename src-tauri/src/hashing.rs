@@ -0,0 +1,32 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use sha2::{Digest, Sha256};
+
+/// Bytes read from each end of the file.
+const CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Fast identity hash of a file: SHA-256 over its size plus the first and
+/// last megabyte. Good enough to recognise the same video after a copy or
+/// rename without reading gigabytes.
+pub fn partial_hash(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+
+    let mut hasher = Sha256::new();
+    hasher.update(len.to_le_bytes());
+
+    let head_len = len.min(CHUNK_SIZE);
+    let mut buffer = vec![0u8; head_len as usize];
+    file.read_exact(&mut buffer).map_err(|e| e.to_string())?;
+    hasher.update(&buffer);
+
+    let tail_start = head_len.max(len.saturating_sub(CHUNK_SIZE));
+    if tail_start < len {
+        file.seek(SeekFrom::Start(tail_start)).map_err(|e| e.to_string())?;
+        buffer.clear();
+        file.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
+        hasher.update(&buffer);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
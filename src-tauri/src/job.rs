@@ -0,0 +1,278 @@
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Child;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use crate::limits;
+use crate::models::JobStatusEvent;
+use crate::tools;
+use crate::LogPayload;
+
+/// How long ffmpeg gets to exit after each step of a stop (`q`, then
+/// SIGTERM) before the next, harsher one.
+const STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// Error returned by everything that stops because the job was cancelled.
+pub const CANCELLED: &str = "Processing was cancelled";
+
+#[derive(Default)]
+struct ProcessState {
+    /// ffmpeg processes currently working on the job.
+    pids: Vec<u32>,
+    paused_since: Option<Instant>,
+    paused_total: Duration,
+}
+
+/// A running encode: cancelled through its token, paused by stopping its
+/// ffmpeg processes. Pausing between processes holds back the next one.
+pub struct Job {
+    app: AppHandle,
+    path: String,
+    token: CancellationToken,
+    process: Mutex<ProcessState>,
+    resumed: Notify,
+}
+
+impl Job {
+    pub fn new(app: &AppHandle, path: &str) -> Arc<Self> {
+        Arc::new(Self {
+            app: app.clone(),
+            path: path.to_string(),
+            token: CancellationToken::new(),
+            process: Mutex::new(ProcessState::default()),
+            resumed: Notify::new(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn emit_status(&self, status: &str) {
+        let _ = self.app.emit("job-status", JobStatusEvent { path: self.path.clone(), status: status.to_string() });
+    }
+
+    pub fn cancel(&self) {
+        self.token.cancel();
+        self.emit_status("cancelling");
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        let mut process = self.process.lock().map_err(|_| "Failed to lock mutex")?;
+        if process.paused_since.is_some() {
+            return Ok(());
+        }
+        for &pid in &process.pids {
+            signal_process(pid, ProcessSignal::Stop)?;
+        }
+        process.paused_since = Some(Instant::now());
+        self.emit_status("paused");
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        let mut process = self.process.lock().map_err(|_| "Failed to lock mutex")?;
+        let Some(since) = process.paused_since else {
+            return Ok(());
+        };
+        for &pid in &process.pids {
+            signal_process(pid, ProcessSignal::Continue)?;
+        }
+        process.paused_since = None;
+        process.paused_total += since.elapsed();
+        self.resumed.notify_waiters();
+        self.emit_status("running");
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.process.lock().map(|p| p.paused_since.is_some()).unwrap_or(false)
+    }
+
+    /// Total time spent paused so far.
+    pub fn paused_duration(&self) -> Duration {
+        self.process.lock()
+            .map(|p| p.paused_total + p.paused_since.map(|since| since.elapsed()).unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    /// Records a running ffmpeg process until the returned guard drops,
+    /// stopping it straight away if the job was paused while it was being
+    /// spawned.
+    fn attach(&self, pid: u32) -> Attached<'_> {
+        if let Ok(mut process) = self.process.lock() {
+            process.pids.push(pid);
+            if process.paused_since.is_some() {
+                let _ = signal_process(pid, ProcessSignal::Stop);
+            }
+        }
+        Attached { job: self, pid }
+    }
+
+    /// Lets a stopped process run again so it can react to being cancelled,
+    /// without ending the paused state.
+    fn wake_for_stop(&self, pid: u32) {
+        if let Ok(process) = self.process.lock() {
+            if process.paused_since.is_some() {
+                let _ = signal_process(pid, ProcessSignal::Continue);
+            }
+        }
+    }
+
+    /// Waits while the job is paused. Fails if it is cancelled meanwhile.
+    async fn wait_while_paused(&self) -> Result<(), String> {
+        loop {
+            let resumed = self.resumed.notified();
+            if self.is_cancelled() {
+                return Err(CANCELLED.to_string());
+            }
+            if !self.is_paused() {
+                return Ok(());
+            }
+            tokio::select! {
+                _ = resumed => {}
+                _ = self.token.cancelled() => {}
+            }
+        }
+    }
+}
+
+/// Keeps a process listed on its job while it runs, even if the future
+/// running it is dropped midway.
+struct Attached<'a> {
+    job: &'a Job,
+    pid: u32,
+}
+
+impl Drop for Attached<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut process) = self.job.process.lock() {
+            process.pids.retain(|&pid| pid != self.pid);
+        }
+    }
+}
+
+/// Makes `job` the one `cancel_processing`, `pause_processing` and
+/// `resume_processing` act on until dropped.
+pub struct CurrentJob<'a> {
+    slot: &'a Mutex<Option<Arc<Job>>>,
+    pub job: Arc<Job>,
+}
+
+impl<'a> CurrentJob<'a> {
+    pub fn start(app: &AppHandle, slot: &'a Mutex<Option<Arc<Job>>>, path: &str) -> Self {
+        let job = Job::new(app, path);
+        if let Ok(mut guard) = slot.lock() {
+            *guard = Some(job.clone());
+        }
+        job.emit_status("running");
+        Self { slot, job }
+    }
+}
+
+impl Drop for CurrentJob<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut guard) = self.slot.lock() {
+            *guard = None;
+        }
+    }
+}
+
+enum ProcessSignal {
+    Stop,
+    Continue,
+}
+
+#[cfg(unix)]
+fn signal_process(pid: u32, signal: ProcessSignal) -> Result<(), String> {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+    let signal = match signal {
+        ProcessSignal::Stop => Signal::SIGSTOP,
+        ProcessSignal::Continue => Signal::SIGCONT,
+    };
+    kill(Pid::from_raw(pid as i32), signal).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn signal_process(_pid: u32, _signal: ProcessSignal) -> Result<(), String> {
+    Err("Pausing a running encode is only supported on Unix".to_string())
+}
+
+#[cfg(unix)]
+fn terminate(child: &Child) {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+    if let Some(pid) = child.id() {
+        let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
+    }
+}
+
+/// Stops ffmpeg, as gently as it will allow: `q` on stdin lets it finish
+/// writing, SIGTERM (Unix only) comes next and SIGKILL last.
+async fn stop(child: &mut Child) {
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(b"q\n").await;
+    }
+    if tokio::time::timeout(STOP_GRACE, child.wait()).await.is_ok() {
+        return;
+    }
+
+    #[cfg(unix)]
+    {
+        terminate(child);
+        if tokio::time::timeout(STOP_GRACE, child.wait()).await.is_ok() {
+            return;
+        }
+    }
+
+    let _ = child.kill().await;
+}
+
+/// Runs ffmpeg with `args` as part of `job`, streaming its stderr to the
+/// processing log of `log_path`. Returns `CANCELLED` without starting when
+/// the job was already cancelled, waits first while it is paused, and stops
+/// ffmpeg when it is cancelled while running.
+pub async fn run_ffmpeg(app: &AppHandle, job: &Job, args: &[String], log_path: &str) -> Result<ExitStatus, String> {
+    job.wait_while_paused().await?;
+
+    let mut child = limits::ffmpeg_command(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(tools::ffmpeg_spawn_error)?;
+    let pid = child.id().ok_or("ffmpeg exited immediately")?;
+    let _attached = job.attach(pid);
+
+    let stderr = child.stderr.take().ok_or("Failed to capture stderr")?;
+    let log_app = app.clone();
+    let log_path = log_path.to_string();
+    let forward = tauri::async_runtime::spawn(async move {
+        let mut reader = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            let _ = log_app.emit("processing-log", LogPayload { path: log_path.clone(), message: line });
+        }
+    });
+
+    let result = tokio::select! {
+        status = child.wait() => status.map_err(|e| e.to_string()),
+        _ = job.token.cancelled() => {
+            job.wake_for_stop(pid);
+            stop(&mut child).await;
+            Err(CANCELLED.to_string())
+        }
+    };
+
+    let _ = forward.await;
+    result
+}
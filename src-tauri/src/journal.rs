@@ -0,0 +1,188 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+use crate::hashing::partial_hash;
+use crate::models::{FileOperation, JournalEntry};
+use crate::move_file;
+use crate::output::numbered_path;
+
+const JOURNAL_FILE: &str = "file_operations.jsonl";
+
+/// Number of entries `list_file_operations` returns by default.
+const DEFAULT_LIST_LIMIT: usize = 100;
+
+/// Serializes appends with the read-modify-write done by undo.
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+fn journal_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
+    Ok(data_dir.join(JOURNAL_FILE))
+}
+
+/// Starts a journal entry for `operation` on `original`, hashing the file
+/// if it still exists.
+pub fn new_entry(operation: FileOperation, original: &Path) -> JournalEntry {
+    JournalEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: chrono::Local::now().to_rfc3339(),
+        operation,
+        original_path: original.to_string_lossy().to_string(),
+        new_path: None,
+        backup_path: None,
+        trashed: false,
+        hash: partial_hash(original).ok(),
+        undone: false,
+    }
+}
+
+/// Appends `entry` to the file operation journal in the app data dir.
+pub fn record(app: &AppHandle, entry: &JournalEntry) -> Result<(), String> {
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    let _guard = JOURNAL_LOCK.lock().map_err(|_| "Failed to lock journal")?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal_path(app)?)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+/// Rewrites the recorded entry with `entry`'s id, e.g. once a later step of
+/// the operation has finished. Appends it if it was never recorded.
+pub fn update(app: &AppHandle, entry: &JournalEntry) -> Result<(), String> {
+    let _guard = JOURNAL_LOCK.lock().map_err(|_| "Failed to lock journal")?;
+    let mut entries = read_journal(app)?;
+    match entries.iter_mut().find(|e| e.id == entry.id) {
+        Some(existing) => *existing = entry.clone(),
+        None => entries.push(entry.clone()),
+    }
+    write_journal(app, &entries)
+}
+
+/// Reverts `entry` straight away because a later step of its operation
+/// failed, and records it as undone.
+pub fn revert(app: &AppHandle, entry: &mut JournalEntry) -> Result<(), String> {
+    undo_entry(entry)?;
+    entry.undone = true;
+    update(app, entry)
+}
+
+fn read_journal(app: &AppHandle) -> Result<Vec<JournalEntry>, String> {
+    let path = journal_path(app)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    // Skip lines that fail to parse (e.g. a write cut short by a crash)
+    Ok(content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+fn write_journal(app: &AppHandle, entries: &[JournalEntry]) -> Result<(), String> {
+    let mut content = String::new();
+    for entry in entries {
+        content.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+        content.push('\n');
+    }
+    let path = journal_path(app)?;
+    let temp_path = path.with_extension("jsonl.tmp");
+    std::fs::write(&temp_path, content).map_err(|e| e.to_string())?;
+    std::fs::rename(&temp_path, &path).map_err(|e| e.to_string())
+}
+
+/// Puts the most recently trashed file that came from `original` back.
+#[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))))]
+fn restore_from_trash(original: &Path) -> Result<(), String> {
+    let item = trash::os_limited::list()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|item| item.original_path() == original)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or_else(|| format!("{} is no longer in the trash", original.display()))?;
+    trash::os_limited::restore_all([item]).map_err(|e| e.to_string())
+}
+
+#[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))))]
+fn restore_from_trash(original: &Path) -> Result<(), String> {
+    Err(format!("Restoring from the trash isn't supported on this platform, restore {} manually", original.display()))
+}
+
+/// Brings the set-aside original back to its old location, checking the
+/// backup copy against the recorded hash first.
+fn restore_original(entry: &JournalEntry, original: &Path) -> Result<(), String> {
+    if original.exists() {
+        return Err(format!("{} already exists", original.display()));
+    }
+
+    if let Some(backup) = &entry.backup_path {
+        let backup = Path::new(backup);
+        if !backup.exists() {
+            return Err(format!("Backup {} no longer exists", backup.display()));
+        }
+        if let Some(hash) = &entry.hash {
+            if partial_hash(backup)? != *hash {
+                return Err(format!("Backup {} has changed since it was made", backup.display()));
+            }
+        }
+        move_file(backup, original)
+    } else if entry.trashed {
+        restore_from_trash(original)
+    } else {
+        Err("No backup or trash copy was kept for this operation".to_string())
+    }
+}
+
+fn undo_entry(entry: &JournalEntry) -> Result<(), String> {
+    let original = Path::new(&entry.original_path);
+
+    match entry.operation {
+        FileOperation::Delete => restore_original(entry, original),
+        FileOperation::Move => {
+            let moved = Path::new(entry.new_path.as_deref().ok_or("Operation has no destination")?);
+            if original.exists() {
+                return Err(format!("{} already exists", original.display()));
+            }
+            move_file(moved, original)
+        }
+        FileOperation::Replace => {
+            // The replacement may have taken the original's name; keep it under a numbered one
+            if let Some(new_path) = &entry.new_path {
+                let new_path = Path::new(new_path);
+                if new_path == original && new_path.exists() {
+                    move_file(new_path, &numbered_path(new_path)?)?;
+                }
+            }
+            restore_original(entry, original)
+        }
+        FileOperation::Tag => Err("Tagging rewrites the original in place and can't be undone".to_string()),
+    }
+}
+
+/// Most recent file operations first.
+#[tauri::command]
+pub fn list_file_operations(app: AppHandle, limit: Option<usize>) -> Result<Vec<JournalEntry>, String> {
+    let _guard = JOURNAL_LOCK.lock().map_err(|_| "Failed to lock journal")?;
+    let mut entries = read_journal(&app)?;
+    entries.reverse();
+    entries.truncate(limit.unwrap_or(DEFAULT_LIST_LIMIT));
+    Ok(entries)
+}
+
+/// Reverts the journaled operation `id` when a backup or trash copy is still available.
+#[tauri::command]
+pub fn undo_file_operation(app: AppHandle, id: String) -> Result<JournalEntry, String> {
+    let _guard = JOURNAL_LOCK.lock().map_err(|_| "Failed to lock journal")?;
+    let mut entries = read_journal(&app)?;
+    let entry = entries.iter_mut().find(|e| e.id == id).ok_or("Unknown operation")?;
+    if entry.undone {
+        return Err("Operation was already undone".to_string());
+    }
+
+    undo_entry(entry)?;
+    entry.undone = true;
+    let undone = entry.clone();
+    write_journal(&app, &entries)?;
+    Ok(undone)
+}
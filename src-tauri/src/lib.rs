@@ -1,14 +1,32 @@
-use std::path::PathBuf;
-use std::process::Stdio;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use tauri::{Emitter, State, AppHandle, Manager};
 use tauri::path::BaseDirectory;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
 
+mod bundle;
+mod diskspace;
+mod hashing;
+mod job;
+mod journal;
+mod limits;
+mod metadata;
 mod models;
-use models::{VideoFilter, VideoModifier, VideoOptions, ProcessingStats};
+mod output;
+mod preview;
+mod processed;
+mod queue;
+mod replace;
+mod scan;
+mod schedule;
+mod scratch;
+mod segments;
+mod settings;
+mod thumbnails;
+mod tools;
+mod warnings;
+mod watch;
+use models::{FileOperation, VideoFilter, VideoModifier, VideoOptions, ProcessingStats};
 
 const DEFAULT_FILTERS: &str = "short_name\tlong_name\tpriority\tcode
 quart\tquarter size\t10\tscale=iw/4:-1
@@ -36,8 +54,15 @@ t\tduration (secs)\t-t #1
 crop\tcrop frame (pixels)\tvf:crop=in_w-2*#1:in_h-2*#1:#1:#1
 ";
 
+/// Filter table in the config dir.
+const FILTERS_FILE: &str = "video_filters.tab";
+
+/// Modifier table in the config dir.
+const MODIFIERS_FILE: &str = "video_commands.tab";
+
 struct AppState {
-    current_pid: Mutex<Option<u32>>,
+    current_job: Mutex<Option<Arc<job::Job>>>,
+    encode_lock: tokio::sync::Mutex<()>,
 }
 
 #[derive(Clone, serde::Serialize)]
@@ -59,12 +84,12 @@ fn ensure_config_files(app: &AppHandle) -> Result<(), String> {
         std::fs::create_dir_all(&config_dir).map_err(|e| e.to_string())?;
     }
 
-    let filters_path = config_dir.join("video_filters.tab");
+    let filters_path = config_dir.join(FILTERS_FILE);
     if !filters_path.exists() {
         std::fs::write(&filters_path, DEFAULT_FILTERS).map_err(|e| e.to_string())?;
     }
 
-    let modifiers_path = config_dir.join("video_commands.tab");
+    let modifiers_path = config_dir.join(MODIFIERS_FILE);
     if !modifiers_path.exists() {
         std::fs::write(&modifiers_path, DEFAULT_MODIFIERS).map_err(|e| e.to_string())?;
     }
@@ -74,7 +99,7 @@ fn ensure_config_files(app: &AppHandle) -> Result<(), String> {
 
 #[tauri::command]
 fn get_filters(app: AppHandle) -> Result<Vec<VideoFilter>, String> {
-    let path = get_config_path(&app, "video_filters.tab")?;
+    let path = get_config_path(&app, FILTERS_FILE)?;
     
     let mut rdr = csv::ReaderBuilder::new()
         .delimiter(b'\t')
@@ -91,7 +116,7 @@ fn get_filters(app: AppHandle) -> Result<Vec<VideoFilter>, String> {
 
 #[tauri::command]
 fn get_modifiers(app: AppHandle) -> Result<Vec<VideoModifier>, String> {
-    let path = get_config_path(&app, "video_commands.tab")?;
+    let path = get_config_path(&app, MODIFIERS_FILE)?;
 
     let mut rdr = csv::ReaderBuilder::new()
         .delimiter(b'\t')
@@ -106,37 +131,48 @@ fn get_modifiers(app: AppHandle) -> Result<Vec<VideoModifier>, String> {
     Ok(modifiers)
 }
 
-#[tauri::command]
-async fn check_file_status(path: String) -> Result<String, String> {
-    let output = Command::new("ffprobe")
-        .args(&[
+/// Returns the container-level metadata tags of a media file (`Null` if it has none).
+async fn probe_format_tags(path: &str) -> Result<serde_json::Value, String> {
+    let output = tools::ffprobe_command()
+        .args([
             "-v", "quiet",
             "-print_format", "json",
             "-show_format",
-            &path,
+            path,
         ])
         .output()
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(tools::ffprobe_spawn_error)?;
 
     if !output.status.success() {
         return Err("Failed to run ffprobe".to_string());
     }
 
     let json_str = String::from_utf8_lossy(&output.stdout);
-    let metadata: serde_json::Value = serde_json::from_str(&json_str).map_err(|e| e.to_string())?;
+    let mut metadata: serde_json::Value = serde_json::from_str(&json_str).map_err(|e| e.to_string())?;
+    Ok(metadata.get_mut("format").and_then(|f| f.get_mut("tags")).map(|t| t.take()).unwrap_or_default())
+}
 
-    if let Some(tags) = metadata.get("format").and_then(|f| f.get("tags")) {
-        // Check for our specific tag
-        if let Some(_) = tags.get("reprocessed") {
+#[tauri::command]
+async fn check_file_status(app: AppHandle, path: String) -> Result<String, String> {
+    // Originals marked without rewriting them (sidecar or database)
+    let recorded_path = PathBuf::from(&path);
+    let recorded_app = app.clone();
+    if tauri::async_runtime::spawn_blocking(move || processed::is_recorded(&recorded_app, &recorded_path)).await.map_err(|e| e.to_string())? {
+        return Ok("skipped".to_string());
+    }
+
+    let tags = probe_format_tags(&path).await?;
+
+    // Check for our specific tag
+    if tags.get("reprocessed").is_some() {
+        return Ok("skipped".to_string());
+    }
+    // Legacy check (optional, but good for backward compatibility if any)
+    if let Some(comment) = tags.get("comment").and_then(|c| c.as_str()) {
+        if comment.contains("PROCESSED_BY_VIDREPROCESS") {
             return Ok("skipped".to_string());
         }
-        // Legacy check (optional, but good for backward compatibility if any)
-        if let Some(comment) = tags.get("comment").and_then(|c| c.as_str()) {
-            if comment.contains("PROCESSED_BY_VIDREPROCESS") {
-                return Ok("skipped".to_string());
-            }
-        }
     }
 
     Ok("pending".to_string())
@@ -144,22 +180,43 @@ async fn check_file_status(path: String) -> Result<String, String> {
 
 #[tauri::command]
 fn cancel_processing(state: State<AppState>) -> Result<(), String> {
-    let mut pid_guard = state.current_pid.lock().map_err(|_| "Failed to lock mutex")?;
-    if let Some(pid) = *pid_guard {
-        // Kill the process
-        let _ = std::process::Command::new("kill")
-            .arg(pid.to_string())
-            .output();
-        *pid_guard = None;
-        Ok(())
-    } else {
-        Ok(()) // No process running
+    let job_guard = state.current_job.lock().map_err(|_| "Failed to lock mutex")?;
+    if let Some(job) = job_guard.as_ref() {
+        job.cancel();
+    }
+    Ok(())
+}
+
+/// Suspends the running ffmpeg process of the current job (Unix only).
+#[tauri::command]
+fn pause_processing(state: State<AppState>) -> Result<(), String> {
+    let job_guard = state.current_job.lock().map_err(|_| "Failed to lock mutex")?;
+    match job_guard.as_ref() {
+        Some(job) => job.pause(),
+        None => Err("Nothing is being processed".to_string()),
+    }
+}
+
+#[tauri::command]
+fn resume_processing(state: State<AppState>) -> Result<(), String> {
+    let job_guard = state.current_job.lock().map_err(|_| "Failed to lock mutex")?;
+    match job_guard.as_ref() {
+        Some(job) => job.resume(),
+        None => Err("Nothing is being processed".to_string()),
     }
 }
 
+/// Moves `path` to the OS trash and journals it so it can be undone. Fails
+/// rather than deleting for good when the trash can't take it.
 #[tauri::command]
-fn delete_file(path: String) -> Result<(), String> {
-    std::fs::remove_file(path).map_err(|e| e.to_string())
+fn delete_file(app: AppHandle, path: String) -> Result<(), String> {
+    let path_buf = PathBuf::from(&path);
+    let mut entry = journal::new_entry(FileOperation::Delete, &path_buf);
+
+    trash::delete(&path_buf).map_err(|e| format!("Failed to move {} to the trash: {}", path, e))?;
+    entry.trashed = true;
+
+    journal::record(&app, &entry).map_err(|e| format!("{} was moved to the trash, but failed to record it in journal: {}", path, e))
 }
 
 #[tauri::command]
@@ -183,94 +240,217 @@ fn move_file(source: &std::path::Path, destination: &std::path::Path) -> Result<
     if std::fs::rename(source, destination).is_ok() {
         return Ok(());
     }
-    // Fallback to copy and delete
+    // Fallback to copy and delete, keeping what a rename would have kept
+    let source_metadata = metadata::FileMetadata::capture(source);
     std::fs::copy(source, destination).map_err(|e| e.to_string())?;
+    if let Ok(source_metadata) = source_metadata {
+        source_metadata.apply(destination);
+    }
     std::fs::remove_file(source).map_err(|e| e.to_string())?;
     Ok(())
 }
 
-#[tauri::command]
-async fn generate_preview(path: String) -> Result<String, String> {
-    let temp_dir = std::env::temp_dir();
-    let output_path = temp_dir.join(format!("preview_{}.jpg", uuid::Uuid::new_v4()));
-
-    let output = Command::new("ffmpeg")
-        .args(&[
-            "-y",
-            "-i", &path,
-            "-ss", "0",
-            "-vframes", "1",
-            "-q:v", "2",
-            &output_path.to_string_lossy()
+/// Escapes a path for use inside a single-quoted filter argument.
+fn escape_filter_path(path: &Path) -> String {
+    path.to_string_lossy().replace("'", "'\\''")
+}
+
+/// Video filters and extra output arguments derived from the selected
+/// stabilization, filters and modifiers.
+struct FilterChain {
+    filters: Vec<String>,
+    modifier_args: Vec<String>,
+}
+
+fn build_filter_chain(app: &AppHandle, options: &VideoOptions, trf_path: Option<&Path>) -> Result<FilterChain, String> {
+    let mut filter_chain = Vec::new();
+    let mut modifier_args = Vec::new();
+    
+    if options.stabilize {
+        if let Some(path) = trf_path {
+            filter_chain.push(format!("vidstabtransform=input='{}':zoom=0:smoothing=10", escape_filter_path(path)));
+        }
+    }
+
+    if !options.filters.is_empty() {
+        let all_filters = get_filters(app.clone())?;
+        
+        let mut selected_filters: Vec<&VideoFilter> = all_filters.iter()
+            .filter(|f| options.filters.contains(&f.short_name))
+            .collect();
+        selected_filters.sort_by_key(|f| f.priority);
+
+        for filter in selected_filters {
+            filter_chain.push(filter.code.clone());
+        }
+    }
+
+    if !options.modifiers.is_empty() {
+        let all_modifiers = get_modifiers(app.clone())?;
+        
+        for (short_name, value) in &options.modifiers {
+            if let Some(modifier_def) = all_modifiers.iter().find(|m| &m.short_name == short_name) {
+                let code = modifier_def.code.replace("#1", value);
+                
+                if let Some(filter_code) = code.strip_prefix("vf:") {
+                    // It's a filter modifier
+                    filter_chain.push(filter_code.to_string());
+                } else {
+                    // It's a standard argument modifier
+                    let parts = shlex::split(&code).ok_or("Failed to parse modifier code")?;
+                    modifier_args.extend(parts);
+                }
+            }
+        }
+    }
+
+    Ok(FilterChain { filters: filter_chain, modifier_args })
+}
+
+/// Video encoding arguments (codec, filters, quality, preset) shared by full
+/// encodes and previews. Argument modifiers such as `-ss`/`-t` are only
+/// included when `with_modifier_args` is set, since previews pick their own
+/// segment.
+fn encoding_args(options: &VideoOptions, chain: &FilterChain, with_modifier_args: bool) -> Vec<String> {
+    let mut args = vec![
+        "-codec:v".to_string(), options.codec.clone(),
+        "-qmin".to_string(), "20".to_string(),
+    ];
+
+    if with_modifier_args {
+        args.extend(chain.modifier_args.iter().cloned());
+    }
+
+    if !chain.filters.is_empty() {
+        args.push("-vf".to_string());
+        args.push(chain.filters.join(","));
+    }
+
+    args.push("-qmax".to_string());
+    args.push(options.quality.to_string());
+    
+    args.push("-preset".to_string());
+    args.push(options.preset.clone());
+
+    args
+}
+
+/// Returns the container duration of a media file in seconds.
+async fn probe_duration(path: &str) -> Result<f64, String> {
+    let output = tools::ffprobe_command()
+        .args([
+            "-v", "quiet",
+            "-print_format", "json",
+            "-show_format",
+            path,
         ])
         .output()
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(tools::ffprobe_spawn_error)?;
 
     if !output.status.success() {
-        return Err(format!("Failed to generate preview: {}", String::from_utf8_lossy(&output.stderr)));
+        return Err("Failed to run ffprobe".to_string());
     }
 
-    let image_data = std::fs::read(&output_path).map_err(|e| e.to_string())?;
-    let _ = std::fs::remove_file(output_path); // Cleanup
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    metadata.get("format")
+        .and_then(|f| f.get("duration"))
+        .and_then(|d| d.as_str())
+        .and_then(|d| d.parse::<f64>().ok())
+        .ok_or_else(|| format!("Could not determine duration of {}", path))
+}
 
-    use base64::{Engine as _, engine::general_purpose};
-    let base64_string = general_purpose::STANDARD.encode(image_data);
-    
-    Ok(format!("data:image/jpeg;base64,{}", base64_string))
+/// Value of the `reprocessed` tag written into outputs, describing the settings used.
+fn reprocessed_tag(options: &VideoOptions) -> String {
+    let mut flags_desc = Vec::new();
+    flags_desc.push(format!("quality={}", options.quality));
+    flags_desc.push(format!("codec={}", options.codec));
+    flags_desc.push(format!("preset={}", options.preset));
+    if options.stabilize {
+        flags_desc.push("stabilize=true".to_string());
+    }
+    if !options.filters.is_empty() {
+        flags_desc.push(format!("filters={}", options.filters.join(",")));
+    }
+    if !options.modifiers.is_empty() {
+        let mods: Vec<String> = options.modifiers.iter().map(|(n, v)| format!("{}:{}", n, v)).collect();
+        flags_desc.push(format!("modifiers={}", mods.join(",")));
+    }
+    flags_desc.join("; ")
 }
 
 #[tauri::command]
 async fn process_video(window: tauri::Window, state: State<'_, AppState>, input_path: String, options: VideoOptions) -> Result<ProcessingStats, String> {
+    encode_file(window.app_handle(), &state, input_path, options).await
+}
+
+/// Encodes one file with `options`. Shared by the `process_video` command and
+/// backend-driven jobs such as the batch queue and watch folders; only one
+/// encode runs at a time.
+async fn encode_file(app: &AppHandle, state: &AppState, input_path: String, options: VideoOptions) -> Result<ProcessingStats, String> {
+    let _encode_guard = state.encode_lock.lock().await;
+    tools::check_capabilities(&options)?;
     let start_time = Instant::now();
     let input_path_buf = PathBuf::from(&input_path);
-    let parent = input_path_buf.parent().ok_or("Invalid input path")?;
     let stem = input_path_buf.file_stem().ok_or("Invalid filename")?.to_string_lossy();
     
     let original_size = std::fs::metadata(&input_path).map_err(|e| e.to_string())?.len();
 
-    // Generate output filename
-    let mut suffix_parts = Vec::new();
-    
-    if options.stabilize {
-        suffix_parts.push("_stabilized".to_string());
-    }
+    let reprocessed_tag = reprocessed_tag(&options);
 
-    if !options.filters.is_empty() {
-        suffix_parts.push(format!("_{}", options.filters.join("_")));
-    }
-    
-    if !options.modifiers.is_empty() {
-        let mod_names: Vec<String> = options.modifiers.iter().map(|(name, _)| name.clone()).collect();
-        suffix_parts.push(format!("_{}", mod_names.join("_")));
+    // Decide what happens to an existing output before spending any time encoding
+    let target = output::resolve_output(&input_path_buf, &options, &reprocessed_tag).await?;
+    app.emit("processing-log", LogPayload { path: input_path.clone(), message: target.describe() }).map_err(|e| e.to_string())?;
+    if target.action == output::CollisionAction::Skip {
+        let new_size = std::fs::metadata(&target.path).map_err(|e| e.to_string())?.len();
+        return Ok(ProcessingStats {
+            duration_secs: start_time.elapsed().as_secs_f64(),
+            original_size,
+            new_size,
+            output_path: target.path.to_string_lossy().to_string(),
+            skipped: true,
+            metadata_warnings: Vec::new(),
+        });
     }
+    let mut final_output_path = target.path.clone();
+    let preserved_tags = metadata::preserved_tags(&probe_format_tags(&input_path).await.unwrap_or_default());
+    let output_dir = final_output_path.parent().ok_or("Invalid output path")?.to_path_buf();
+    std::fs::create_dir_all(&output_dir).map_err(|e| e.to_string())?;
     
-    suffix_parts.push(format!("_q{}_{}", options.quality, options.codec));
-    let suffixes = suffix_parts.join("");
-    let output_filename = format!("{}{}.mp4", stem, suffixes);
-    let final_output_path = parent.join(&output_filename);
-    
-    // Use system temp dir for intermediate file
-    let temp_dir = std::env::temp_dir();
-    let temp_output_path = temp_dir.join(format!("{}_{}_workinprogress.mp4", stem, uuid::Uuid::new_v4()));
+    // Intermediate files go to the scratch dir (the output dir unless configured)
+    // and are removed when their guards drop, however the encode ends
+    let temp_dir = scratch::scratch_dir(app, &options, &output_dir)?;
+    let temp_output = scratch::TempFile::new(temp_dir.join(format!("{}_{}_workinprogress.mp4", stem, uuid::Uuid::new_v4())));
+    let temp_output_path = temp_output.path();
 
-    // Stabilization Pass 1
-    let trf_path = if options.stabilize {
+    let trf_file = if options.stabilize {
         let trf_filename = format!("{}_{}.trf", stem, uuid::Uuid::new_v4());
-        Some(temp_dir.join(&trf_filename))
+        Some(scratch::TempFile::new(temp_dir.join(&trf_filename)))
     } else {
         None
     };
+    let trf_path = trf_file.as_ref().map(|f| f.path());
+    let chain = build_filter_chain(app, &options, trf_path)?;
+
+    // Fail now rather than have ffmpeg run out of space halfway through
+    let source_duration = probe_duration(&input_path).await.unwrap_or(0.0);
+    diskspace::check_free_space(&input_path_buf, original_size, source_duration, &options, &chain.modifier_args, &temp_dir, &output_dir)?;
+
+    // Cancelled through `cancel_processing`
+    let current = job::CurrentJob::start(app, &state.current_job, &input_path);
+    let job = &current.job;
+
+    // Stabilization Pass 1
 
     if options.stabilize {
-        if let Some(path) = &trf_path {
-            window.emit("processing-log", LogPayload { path: input_path.clone(), message: "Starting Stabilization Pass 1/2...".to_string() }).map_err(|e| e.to_string())?;
+        if let Some(path) = trf_path {
+            app.emit("processing-log", LogPayload { path: input_path.clone(), message: "Starting Stabilization Pass 1/2...".to_string() }).map_err(|e| e.to_string())?;
             
             // Escape path for filter string: wrap in single quotes and escape existing single quotes
-            let path_str = path.to_string_lossy().replace("'", "'\\''");
+            let path_str = escape_filter_path(path);
             
             let mut args_pass1 = Vec::new();
-            args_pass1.push("-y".to_string());
+            args_pass1.push("-n".to_string());
             args_pass1.push("-i".to_string());
             args_pass1.push(input_path.clone());
             args_pass1.push("-vf".to_string());
@@ -280,248 +460,129 @@ async fn process_video(window: tauri::Window, state: State<'_, AppState>, input_
             args_pass1.push("-".to_string());
 
             let command_str = format!("Command Pass 1: ffmpeg {}", args_pass1.join(" "));
-            window.emit("processing-log", LogPayload { path: input_path.clone(), message: command_str }).map_err(|e| e.to_string())?;
-
-            let mut cmd_pass1 = Command::new("ffmpeg")
-                .args(&args_pass1)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-                .map_err(|e| e.to_string())?;
-
-            if let Some(pid) = cmd_pass1.id() {
-                if let Ok(mut guard) = state.current_pid.lock() {
-                    *guard = Some(pid);
-                }
-            }
-
-            let stderr = cmd_pass1.stderr.take().ok_or("Failed to capture stderr")?;
-            let mut reader = BufReader::new(stderr).lines();
-
-            while let Some(line) = reader.next_line().await.map_err(|e| e.to_string())? {
-                 window.emit("processing-log", LogPayload { path: input_path.clone(), message: line }).map_err(|e| e.to_string())?;
-            }
+            app.emit("processing-log", LogPayload { path: input_path.clone(), message: command_str }).map_err(|e| e.to_string())?;
 
-            let status = cmd_pass1.wait().await.map_err(|e| e.to_string())?;
+            let status = job::run_ffmpeg(app, job, &args_pass1, &input_path).await?;
             
             if !status.success() {
                 return Err(format!("Stabilization Pass 1 failed. Status: {}", status));
             }
             
-            window.emit("processing-log", LogPayload { path: input_path.clone(), message: "Stabilization Pass 1 Complete. Starting Pass 2...".to_string() }).map_err(|e| e.to_string())?;
+            app.emit("processing-log", LogPayload { path: input_path.clone(), message: "Stabilization Pass 1 Complete. Starting Pass 2...".to_string() }).map_err(|e| e.to_string())?;
         }
     }
 
-    // Build flags string for metadata
-    let mut flags_desc = Vec::new();
-    flags_desc.push(format!("quality={}", options.quality));
-    flags_desc.push(format!("codec={}", options.codec));
-    flags_desc.push(format!("preset={}", options.preset));
-    if options.stabilize {
-        flags_desc.push("stabilize=true".to_string());
-    }
-    if !options.filters.is_empty() {
-        flags_desc.push(format!("filters={}", options.filters.join(",")));
-    }
-    if !options.modifiers.is_empty() {
-        let mods: Vec<String> = options.modifiers.iter().map(|(n, v)| format!("{}:{}", n, v)).collect();
-        flags_desc.push(format!("modifiers={}", mods.join(",")));
+    let encoding = encoding_args(&options, &chain, true);
+
+    // Metadata tags
+    let mut tag_args = vec![
+        "-metadata".to_string(), format!("reprocessed={}", reprocessed_tag),
+        "-metadata".to_string(), "comment=PROCESSED_BY_VIDREPROCESS".to_string(), // Keep legacy tag for now
+    ];
+
+    // Creation time and GPS location, which filters or the MP4 muxer may drop
+    for (key, value) in &preserved_tags {
+        tag_args.push("-metadata".to_string());
+        tag_args.push(format!("{}={}", key, value));
     }
-    let reprocessed_tag = flags_desc.join("; ");
 
-    // Build ffmpeg command (Pass 2 or Single Pass)
-    let mut args = Vec::new();
-    
-    // 1. Global options
-    args.push("-y".to_string());
-    
-    if options.hwaccel != "none" {
-        args.push("-hwaccel".to_string());
-        args.push(options.hwaccel.clone());
+    let segment_blocker = segments::unsupported_reason(&options, &chain.modifier_args);
+    if let (true, Some(reason)) = (options.segmented, segment_blocker) {
+        app.emit("processing-log", LogPayload { path: input_path.clone(), message: format!("Encoding without segments: {}", reason) }).map_err(|e| e.to_string())?;
     }
 
-    // 2. Input
-    args.push("-i".to_string());
-    args.push(input_path.clone());
+    // Execute (a cancelled pass 1 never gets here; one cancelled since won't start)
+    let status = if options.segmented && segment_blocker.is_none() {
+        segments::encode_segmented(app, job, &input_path_buf, &options, &encoding, temp_output_path, &tag_args).await?
+    } else {
+        // Build ffmpeg command (Pass 2 or Single Pass)
+        let mut args = Vec::new();
 
-    // 3. Encoding Options
-    args.push("-map_metadata".to_string()); args.push("0".to_string()); // Copy global metadata
-    
-    // Audio Copy
-    args.push("-c:a".to_string()); args.push("copy".to_string());
-    
-    args.push("-codec:v".to_string()); args.push(options.codec.clone());
-    args.push("-qmin".to_string()); args.push("20".to_string());
+        // 1. Global options (intermediate names are unique, never clobber anything)
+        args.push("-n".to_string());
 
-    // Filters & Stabilization
-    let mut filter_chain = Vec::new();
-    
-    if options.stabilize {
-        if let Some(path) = &trf_path {
-             let path_str = path.to_string_lossy().replace("'", "'\\''");
-             filter_chain.push(format!("vidstabtransform=input='{}':zoom=0:smoothing=10", path_str));
+        if options.hwaccel != "none" {
+            args.push("-hwaccel".to_string());
+            args.push(options.hwaccel.clone());
         }
-    }
 
-    if !options.filters.is_empty() {
-        let app_handle = window.app_handle();
-        let all_filters = get_filters(app_handle.clone())?;
-        
-        let mut selected_filters: Vec<&VideoFilter> = all_filters.iter()
-            .filter(|f| options.filters.contains(&f.short_name))
-            .collect();
-        selected_filters.sort_by_key(|f| f.priority);
+        // 2. Input
+        args.push("-i".to_string());
+        args.push(input_path.clone());
 
-        for filter in selected_filters {
-            filter_chain.push(filter.code.clone());
-        }
-    }
+        // 3. Encoding Options
+        args.push("-map_metadata".to_string()); args.push("0".to_string()); // Copy global metadata
 
-    // Modifiers Logic (Fixed)
-    if !options.modifiers.is_empty() {
-        let app_handle = window.app_handle();
-        let all_modifiers = get_modifiers(app_handle.clone())?;
-        
-        for (short_name, value) in options.modifiers {
-            if let Some(modifier_def) = all_modifiers.iter().find(|m| m.short_name == short_name) {
-                let code = modifier_def.code.replace("#1", &value);
-                
-                if code.starts_with("vf:") {
-                    // It's a filter modifier
-                    let filter_code = code.strip_prefix("vf:").unwrap_or(&code);
-                    filter_chain.push(filter_code.to_string());
-                } else {
-                    // It's a standard argument modifier
-                    let parts = shlex::split(&code).ok_or("Failed to parse modifier code")?;
-                    args.extend(parts);
-                }
-            }
-        }
-    }
-    
-    if !filter_chain.is_empty() {
-        args.push("-vf".to_string());
-        args.push(filter_chain.join(","));
-    }
+        // Audio Copy
+        args.push("-c:a".to_string()); args.push("copy".to_string());
 
-    args.push("-qmax".to_string());
-    args.push(options.quality.to_string());
-    
-    args.push("-preset".to_string());
-    args.push(options.preset);
-    
-    args.push("-movflags".to_string());
-    args.push("+faststart".to_string());
-    
-    // Metadata tags
-    args.push("-metadata".to_string());
-    args.push(format!("reprocessed={}", reprocessed_tag));
-    
-    args.push("-metadata".to_string());
-    args.push("comment=PROCESSED_BY_VIDREPROCESS".to_string()); // Keep legacy tag for now
-    
-    // 4. Output file
-    args.push(temp_output_path.to_string_lossy().to_string());
-
-    // Log the command
-    let command_str = format!("Command: ffmpeg {}", args.join(" "));
-    window.emit("processing-log", LogPayload { path: input_path.clone(), message: command_str }).map_err(|e| e.to_string())?;
-
-    // Execute
-    let mut cmd = Command::new("ffmpeg")
-        .args(&args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| e.to_string())?;
+        args.extend(encoding);
 
-    // Store PID
-    if let Some(pid) = cmd.id() {
-        if let Ok(mut guard) = state.current_pid.lock() {
-            *guard = Some(pid);
-        }
-    }
+        args.push("-movflags".to_string());
+        args.push("+faststart".to_string());
 
-    let stderr = cmd.stderr.take().ok_or("Failed to capture stderr")?;
-    let mut reader = BufReader::new(stderr).lines();
+        args.extend(tag_args);
 
-    while let Some(line) = reader.next_line().await.map_err(|e| e.to_string())? {
-        window.emit("processing-log", LogPayload { path: input_path.clone(), message: line }).map_err(|e| e.to_string())?;
-    }
+        // 4. Output file
+        args.push(temp_output_path.to_string_lossy().to_string());
 
-    let status = cmd.wait().await.map_err(|e| e.to_string())?;
+        // Log the command
+        let command_str = format!("Command: ffmpeg {}", args.join(" "));
+        app.emit("processing-log", LogPayload { path: input_path.clone(), message: command_str }).map_err(|e| e.to_string())?;
 
-    // Clear PID
-    if let Ok(mut guard) = state.current_pid.lock() {
-        *guard = None;
-    }
+        job::run_ffmpeg(app, job, &args, &input_path).await?
+    };
 
     // Cleanup TRF file
-    if let Some(path) = &trf_path {
-        if path.exists() {
-            let _ = std::fs::remove_file(path);
-        }
-    }
+    drop(trf_file);
 
     if status.success() {
-        move_file(&temp_output_path, &final_output_path).map_err(|e| e.to_string())?;
+        // Something may have claimed the name while we were encoding
+        if final_output_path.exists() && target.action != output::CollisionAction::Overwrite {
+            let taken = final_output_path;
+            final_output_path = output::numbered_path(&taken)?;
+            app.emit("processing-log", LogPayload { path: input_path.clone(), message: format!("Output {} appeared during encoding, writing to {} instead", taken.display(), final_output_path.display()) }).map_err(|e| e.to_string())?;
+        }
+        move_file(temp_output_path, &final_output_path).map_err(|e| e.to_string())?;
         
-        // Copy timestamps
-        if let Ok(metadata) = std::fs::metadata(&input_path) {
-            if let (Ok(atime), Ok(mtime)) = (metadata.accessed(), metadata.modified()) {
-                let _ = filetime::set_file_times(&final_output_path, filetime::FileTime::from_system_time(atime), filetime::FileTime::from_system_time(mtime));
-            }
+        // Copy timestamps, permissions, ownership and xattrs, then check the container tags survived
+        let mut metadata_warnings = match metadata::FileMetadata::capture(&input_path_buf) {
+            Ok(source) => source.apply(&final_output_path),
+            Err(e) => vec![format!("filesystem metadata: {}", e)],
+        };
+        let output_tags = probe_format_tags(&final_output_path.to_string_lossy()).await.unwrap_or_default();
+        metadata_warnings.extend(metadata::missing_tags(&preserved_tags, &output_tags));
+        for warning in &metadata_warnings {
+            app.emit("processing-log", LogPayload { path: input_path.clone(), message: format!("Could not preserve {}", warning) }).map_err(|e| e.to_string())?;
         }
         
         let new_size = std::fs::metadata(&final_output_path).map_err(|e| e.to_string())?.len();
-        let duration_secs = start_time.elapsed().as_secs_f64();
+        let duration_secs = start_time.elapsed().saturating_sub(job.paused_duration()).as_secs_f64();
 
-        // Tag Original Logic
-        if options.tag_original {
-            window.emit("processing-log", LogPayload { path: input_path.clone(), message: "Tagging original file...".to_string() }).map_err(|e| e.to_string())?;
-            
-            let temp_tag_path = parent.join(format!("{}_tagged_temp.mp4", stem));
-            
-            let tag_args = vec![
-                "-y".to_string(),
-                "-i".to_string(), input_path.clone(),
-                "-c".to_string(), "copy".to_string(),
-                "-map_metadata".to_string(), "0".to_string(),
-                "-metadata".to_string(), "reprocessed=tagged_as_processed".to_string(),
-                temp_tag_path.to_string_lossy().to_string()
-            ];
-
-            let tag_output = Command::new("ffmpeg")
-                .args(&tag_args)
-                .output()
-                .await
-                .map_err(|e| e.to_string())?;
-
-            if tag_output.status.success() {
-                // Replace original with tagged
-                if let Err(e) = move_file(&temp_tag_path, &PathBuf::from(&input_path)) {
-                    window.emit("processing-log", LogPayload { path: input_path.clone(), message: format!("Failed to replace original file with tagged version: {}", e) }).map_err(|e| e.to_string())?;
-                    let _ = std::fs::remove_file(&temp_tag_path); // Cleanup
-                } else {
-                     window.emit("processing-log", LogPayload { path: input_path.clone(), message: "Original file successfully tagged.".to_string() }).map_err(|e| e.to_string())?;
-                }
-            } else {
-                window.emit("processing-log", LogPayload { path: input_path.clone(), message: format!("Failed to tag original file: {}", String::from_utf8_lossy(&tag_output.stderr)) }).map_err(|e| e.to_string())?;
-                let _ = std::fs::remove_file(&temp_tag_path); // Cleanup
-            }
+        // Tag Original Logic (pointless when the original is about to be replaced)
+        if options.tag_original && !options.replace_original {
+            processed::mark_processed(app, &input_path_buf, &options, &reprocessed_tag, &final_output_path).await;
+        }
+
+        // Identify the input as it is now, before a replacement moves it away
+        let fingerprint = processed::Fingerprint::of(&input_path_buf);
+
+        if options.replace_original {
+            final_output_path = replace::replace_original(app, &input_path_buf, &final_output_path, &options).await?;
         }
 
-        Ok(ProcessingStats {
+        let stats = ProcessingStats {
             duration_secs,
             original_size,
             new_size,
             output_path: final_output_path.to_string_lossy().to_string(),
-        })
+            skipped: false,
+            metadata_warnings,
+        };
+        processed::record_encode(app, &input_path_buf, fingerprint, &options, &reprocessed_tag, &stats);
+        Ok(stats)
     } else {
-        if temp_output_path.exists() {
-            let _ = std::fs::remove_file(temp_output_path);
-        }
-        Err(format!("FFmpeg failed or was aborted. Status: {}", status))
+        Err(format!("FFmpeg failed. Status: {}", status))
     }
 }
 
@@ -530,9 +591,20 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
-        .manage(AppState { current_pid: Mutex::new(None) })
+        .manage(AppState { current_job: Mutex::new(None), encode_lock: tokio::sync::Mutex::new(()) })
+        .register_uri_scheme_protocol(thumbnails::THUMBNAIL_SCHEME, thumbnails::handle_protocol)
         .setup(|app| {
             ensure_config_files(app.handle())?;
+            let thumbnail_dir = app.path().app_cache_dir()?.join("thumbnails");
+            app.manage(thumbnails::ThumbnailCache::new(thumbnail_dir, thumbnails::THUMBNAIL_CACHE_LIMIT));
+            settings::init(app.handle());
+            limits::init(app.handle());
+            tools::init(app.handle());
+            processed::init(app.handle());
+            scratch::init(app.handle());
+            schedule::init(app.handle());
+            queue::init(app.handle());
+            watch::init(app.handle());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -541,11 +613,38 @@ pub fn run() {
             check_file_status, 
             process_video,
             cancel_processing,
+            pause_processing,
+            resume_processing,
             delete_file,
             show_in_folder,
             open_file,
             save_text_file,
-            generate_preview
+            preview::generate_preview,
+            preview::preview_encode,
+            preview::generate_contact_sheet,
+            scan::scan_directory,
+            journal::list_file_operations,
+            journal::undo_file_operation,
+            limits::get_resource_limits,
+            limits::set_resource_limits,
+            queue::enqueue_files,
+            queue::clear_queue,
+            queue::get_queue,
+            schedule::get_schedule,
+            schedule::set_schedule,
+            schedule::get_schedule_status,
+            bundle::export_config_bundle,
+            bundle::import_config_bundle,
+            settings::load_settings,
+            settings::save_settings,
+            settings::save_default_options,
+            tools::get_ffmpeg_paths,
+            tools::set_ffmpeg_paths,
+            tools::get_ffmpeg_info,
+            tools::check_ffmpeg,
+            warnings::get_config_warnings,
+            watch::get_watch_folders,
+            watch::set_watch_folders
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
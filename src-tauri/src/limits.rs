@@ -0,0 +1,178 @@
+use std::sync::{OnceLock, RwLock};
+use tauri::AppHandle;
+use tokio::process::Command;
+
+use crate::models::ResourceLimits;
+use crate::{settings, tools};
+
+/// Limits applied to every ffmpeg process, loaded at startup.
+static LIMITS: RwLock<ResourceLimits> = RwLock::new(ResourceLimits {
+    nice: None,
+    io_class: None,
+    io_level: None,
+    threads: None,
+    cpu_quota: None,
+});
+
+/// Whether `systemd-run --user` works here, probed once at startup. It
+/// needs a user session bus, which e.g. SSH sessions and containers lack.
+#[cfg(target_os = "linux")]
+static SYSTEMD_USER_SCOPE: OnceLock<bool> = OnceLock::new();
+
+fn current() -> ResourceLimits {
+    LIMITS.read().map(|limits| limits.clone()).unwrap_or_default()
+}
+
+/// True if `program` can be found on `PATH`.
+#[cfg(target_os = "linux")]
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// Starts an empty user scope to see whether a CPU quota can be enforced.
+#[cfg(target_os = "linux")]
+fn probe_systemd_user_scope() -> bool {
+    on_path("systemd-run")
+        && std::process::Command::new("systemd-run")
+            .args(["--user", "--scope", "--quiet", "--", "true"])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+}
+
+/// Programs that run ffmpeg in turn (`systemd-run`, `nice`, `ionice`), each
+/// exec'ing the next so ffmpeg keeps the spawned pid. Wrappers that aren't
+/// installed or usable are left out, and `ionice` is told to go ahead even
+/// when the class can't be set (realtime needs privileges).
+#[cfg(target_os = "linux")]
+fn wrappers(limits: &ResourceLimits) -> Vec<String> {
+    let mut wrapper = Vec::new();
+    if let Some(quota) = limits.cpu_quota.filter(|_| SYSTEMD_USER_SCOPE.get() == Some(&true)) {
+        wrapper.extend([
+            "systemd-run".to_string(), "--user".to_string(), "--scope".to_string(), "--quiet".to_string(),
+            "-p".to_string(), format!("CPUQuota={}%", quota),
+            "--".to_string(),
+        ]);
+    }
+    if let Some(nice) = limits.nice.filter(|_| on_path("nice")) {
+        wrapper.extend(["nice".to_string(), "-n".to_string(), nice.to_string()]);
+    }
+    if let Some(class) = limits.io_class.filter(|_| on_path("ionice")) {
+        wrapper.extend(["ionice".to_string(), "-t".to_string(), "-c".to_string(), class.to_string()]);
+        // Levels only exist for the realtime and best-effort classes
+        if let (Some(level), 1 | 2) = (limits.io_level, class) {
+            wrapper.extend(["-n".to_string(), level.to_string()]);
+        }
+    }
+    wrapper
+}
+
+#[cfg(not(target_os = "linux"))]
+fn wrappers(_limits: &ResourceLimits) -> Vec<String> {
+    Vec::new()
+}
+
+/// Caps encoder threads by adding `-threads` (and the x264/x265 equivalent
+/// when the encode doesn't set its own params) in front of the output.
+fn with_thread_cap(args: &[String], threads: u32) -> Vec<String> {
+    let Some((output, options)) = args.split_last() else {
+        return args.to_vec();
+    };
+    let mut capped = options.to_vec();
+    capped.extend(["-threads".to_string(), threads.to_string()]);
+
+    let codec = args.windows(2)
+        .find(|pair| matches!(pair[0].as_str(), "-codec:v" | "-c:v" | "-vcodec"))
+        .map(|pair| pair[1].as_str());
+    let has_param = |flag: &str| args.iter().any(|arg| arg == flag);
+    match codec {
+        Some("libx264") if !has_param("-x264-params") => {
+            capped.extend(["-x264-params".to_string(), format!("threads={}", threads)]);
+        }
+        Some("libx265") if !has_param("-x265-params") => {
+            capped.extend(["-x265-params".to_string(), format!("pools={}", threads)]);
+        }
+        _ => {}
+    }
+
+    capped.push(output.clone());
+    capped
+}
+
+/// The configured ffmpeg with `args`, run under the configured priority, IO class,
+/// CPU quota and thread cap. Every ffmpeg the backend starts goes through here.
+pub fn ffmpeg_command(args: &[String]) -> Command {
+    let limits = current();
+    let args = match limits.threads.filter(|t| *t > 0) {
+        Some(threads) => with_thread_cap(args, threads),
+        None => args.to_vec(),
+    };
+
+    let mut wrapper = wrappers(&limits);
+    wrapper.push(tools::ffmpeg_program().to_string_lossy().to_string());
+    let mut command = Command::new(&wrapper[0]);
+    command.args(&wrapper[1..]).args(&args);
+
+    // No nice on Windows; a positive level means below normal priority there
+    #[cfg(windows)]
+    if limits.nice.is_some_and(|nice| nice > 0) {
+        const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+        command.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+    }
+
+    command
+}
+
+/// Uses `limits` for every ffmpeg started from now on.
+pub fn apply(limits: ResourceLimits) -> Result<(), String> {
+    let mut current = LIMITS.write().map_err(|_| "Failed to lock resource limits")?;
+    *current = limits;
+    Ok(())
+}
+
+pub fn validate(limits: &ResourceLimits) -> Result<(), String> {
+    if limits.nice.is_some_and(|nice| !(-20..=19).contains(&nice)) {
+        return Err("Nice level must be between -20 and 19".to_string());
+    }
+    if limits.io_class.is_some_and(|class| !(1..=3).contains(&class)) {
+        return Err("IO class must be 1 (realtime), 2 (best-effort) or 3 (idle)".to_string());
+    }
+    if limits.io_level.is_some_and(|level| level > 7) {
+        return Err("IO priority level must be between 0 and 7".to_string());
+    }
+    Ok(())
+}
+
+/// Loads the saved resource limits and checks in the background whether a
+/// CPU quota can be enforced.
+pub fn init(app: &AppHandle) {
+    let _ = apply(settings::load(app).map(|s| s.limits).unwrap_or_default());
+
+    #[cfg(target_os = "linux")]
+    {
+        let app = app.clone();
+        std::thread::spawn(move || {
+            let usable = probe_systemd_user_scope();
+            let _ = SYSTEMD_USER_SCOPE.set(usable);
+            if !usable && current().cpu_quota.is_some() {
+                crate::warnings::report(&app, settings::SETTINGS_FILE, "systemd-run --user isn't usable here, the CPU quota won't be enforced".to_string());
+            }
+        });
+    }
+}
+
+#[tauri::command]
+pub fn get_resource_limits() -> ResourceLimits {
+    current()
+}
+
+/// Saves `limits`; they apply to every ffmpeg started from now on.
+#[tauri::command]
+pub fn set_resource_limits(app: AppHandle, limits: ResourceLimits) -> Result<(), String> {
+    validate(&limits)?;
+    settings::update(&app, |settings| settings.limits = limits.clone())?;
+    apply(limits)
+}
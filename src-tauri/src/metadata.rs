@@ -0,0 +1,133 @@
+use std::path::Path;
+use filetime::FileTime;
+
+use crate::move_file;
+
+/// Container tags that `-map_metadata` alone doesn't reliably carry over.
+const LOCATION_TAGS: &[&str] = &["location", "location-eng", "com.apple.quicktime.location.ISO6709"];
+
+/// Filesystem metadata of a file, captured so it can be applied to another
+/// file or restored after the same path was rewritten.
+pub struct FileMetadata {
+    accessed: Option<FileTime>,
+    modified: Option<FileTime>,
+    permissions: std::fs::Permissions,
+    #[cfg(unix)]
+    owner: (u32, u32),
+    #[cfg(unix)]
+    xattrs: Vec<(std::ffi::OsString, Vec<u8>)>,
+}
+
+impl FileMetadata {
+    pub fn capture(path: &Path) -> Result<Self, String> {
+        let metadata = std::fs::metadata(path).map_err(|e| e.to_string())?;
+        Ok(Self {
+            accessed: metadata.accessed().ok().map(FileTime::from_system_time),
+            modified: metadata.modified().ok().map(FileTime::from_system_time),
+            permissions: metadata.permissions(),
+            #[cfg(unix)]
+            owner: {
+                use std::os::unix::fs::MetadataExt;
+                (metadata.uid(), metadata.gid())
+            },
+            #[cfg(unix)]
+            xattrs: read_xattrs(path),
+        })
+    }
+
+    /// Restores only atime/mtime.
+    pub fn apply_times(&self, path: &Path) -> Result<(), String> {
+        match (self.accessed, self.modified) {
+            (Some(atime), Some(mtime)) => filetime::set_file_times(path, atime, mtime).map_err(|e| e.to_string()),
+            (None, Some(mtime)) => filetime::set_file_mtime(path, mtime).map_err(|e| e.to_string()),
+            _ => Err("Source timestamps unavailable".to_string()),
+        }
+    }
+
+    /// Applies everything captured to `path`, returning a description of
+    /// each part that couldn't be preserved.
+    pub fn apply(&self, path: &Path) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        #[cfg(unix)]
+        {
+            if let Err(e) = self.apply_owner(path) {
+                problems.push(format!("ownership: {}", e));
+            }
+            for (name, value) in &self.xattrs {
+                if let Err(e) = xattr::set(path, name, value) {
+                    problems.push(format!("extended attribute {}: {}", name.to_string_lossy(), e));
+                }
+            }
+        }
+
+        // After chown, which clears setuid/setgid bits
+        if let Err(e) = std::fs::set_permissions(path, self.permissions.clone()) {
+            problems.push(format!("permissions: {}", e));
+        }
+
+        // Last, as the steps above may touch the file
+        if let Err(e) = self.apply_times(path) {
+            problems.push(format!("timestamps: {}", e));
+        }
+
+        problems
+    }
+
+    #[cfg(unix)]
+    fn apply_owner(&self, path: &Path) -> Result<(), String> {
+        use std::os::unix::fs::MetadataExt;
+        let current = std::fs::metadata(path).map_err(|e| e.to_string())?;
+        if (current.uid(), current.gid()) == self.owner {
+            return Ok(());
+        }
+        std::os::unix::fs::chown(path, Some(self.owner.0), Some(self.owner.1)).map_err(|e| e.to_string())
+    }
+}
+
+/// Moves `replacement` over `path` and gives it back `original`, the
+/// metadata captured from `path` before it was read or rewritten. Operations
+/// that rewrite a file in place go through here so the file keeps its dates.
+/// Returns what couldn't be restored.
+pub fn replace_in_place(path: &Path, replacement: &Path, original: &FileMetadata) -> Result<Vec<String>, String> {
+    move_file(replacement, path)?;
+    Ok(original.apply(path))
+}
+
+#[cfg(unix)]
+fn read_xattrs(path: &Path) -> Vec<(std::ffi::OsString, Vec<u8>)> {
+    let Ok(names) = xattr::list(path) else { return Vec::new() };
+    names
+        .filter_map(|name| {
+            let value = xattr::get(path, &name).ok().flatten()?;
+            Some((name, value))
+        })
+        .collect()
+}
+
+fn tag<'a>(tags: &'a serde_json::Value, key: &str) -> Option<&'a str> {
+    tags.get(key).and_then(|v| v.as_str()).filter(|v| !v.is_empty())
+}
+
+/// `-metadata` values that keep the source's `creation_time` and GPS
+/// location (e.g. from phone footage) in the MP4 output.
+pub fn preserved_tags(source_tags: &serde_json::Value) -> Vec<(String, String)> {
+    let mut tags = Vec::new();
+    if let Some(creation_time) = tag(source_tags, "creation_time") {
+        tags.push(("creation_time".to_string(), creation_time.to_string()));
+    }
+    // The MP4 muxer only writes location from the plain `location` key
+    if let Some(location) = LOCATION_TAGS.iter().find_map(|key| tag(source_tags, key)) {
+        tags.push(("location".to_string(), location.to_string()));
+    }
+    tags
+}
+
+/// Entries of `expected` that are missing from the output's tags.
+pub fn missing_tags(expected: &[(String, String)], output_tags: &serde_json::Value) -> Vec<String> {
+    expected
+        .iter()
+        .filter(|(key, _)| tag(output_tags, key).is_none())
+        .map(|(key, _)| format!("container tag {}", key))
+        .collect()
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct VideoFilter {
     pub short_name: String,
     pub long_name: String,
@@ -8,14 +8,14 @@ pub struct VideoFilter {
     pub code: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct VideoModifier {
     pub short_name: String,
     pub long_name: String,
     pub code: String,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
 pub struct VideoOptions {
     pub filters: Vec<String>,
     pub modifiers: Vec<(String, String)>,
@@ -24,7 +24,154 @@ pub struct VideoOptions {
     pub preset: String,
     pub hwaccel: String,
     pub tag_original: bool,
+    #[serde(default)]
+    pub tag_mode: TagMode,
     pub stabilize: bool,
+    pub output_dir: Option<String>,
+    pub mirror_root: Option<String>,
+    pub filename_template: Option<String>,
+    #[serde(default)]
+    pub on_collision: CollisionPolicy,
+    #[serde(default)]
+    pub replace_original: bool,
+    pub backup_dir: Option<String>,
+    /// Where intermediate files go; defaults to the output directory.
+    pub scratch_dir: Option<String>,
+    /// Encode in resumable keyframe-aligned segments.
+    #[serde(default)]
+    pub segmented: bool,
+    pub segment_secs: Option<f64>,
+    /// Number of segments encoded in parallel.
+    pub segment_workers: Option<usize>,
+}
+
+impl Default for VideoOptions {
+    fn default() -> Self {
+        Self {
+            filters: Vec::new(),
+            modifiers: Vec::new(),
+            quality: 23,
+            codec: "libx264".to_string(),
+            preset: "medium".to_string(),
+            hwaccel: "none".to_string(),
+            tag_original: false,
+            tag_mode: TagMode::default(),
+            stabilize: false,
+            output_dir: None,
+            mirror_root: None,
+            filename_template: None,
+            on_collision: CollisionPolicy::default(),
+            replace_original: false,
+            backup_dir: None,
+            scratch_dir: None,
+            segmented: false,
+            segment_secs: None,
+            segment_workers: None,
+        }
+    }
+}
+
+/// Application settings kept in `settings.json`, shared by the GUI and
+/// anything else driving the backend.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Settings {
+    /// Layout version, see `settings::SETTINGS_VERSION`.
+    pub version: u32,
+    /// Options new sessions start with, including the scratch dir, segment
+    /// concurrency and output policies.
+    #[serde(default)]
+    pub defaults: VideoOptions,
+    #[serde(default)]
+    pub ffmpeg: ToolPaths,
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+    /// Priority and resource caps for ffmpeg, see `limits`.
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    /// When queued encodes may run, see `schedule`.
+    #[serde(default)]
+    pub schedule: Schedule,
+    #[serde(default)]
+    pub watch_folders: Vec<WatchFolder>,
+}
+
+/// Named set of options users can switch to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub options: VideoOptions,
+}
+
+/// Filters, modifiers, presets and settings exported as one file, so every
+/// workstation can be set up the same way.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConfigBundle {
+    /// Layout version, see `bundle::BUNDLE_VERSION`.
+    pub version: u32,
+    pub exported_at: String,
+    pub filters: Vec<VideoFilter>,
+    pub modifiers: Vec<VideoModifier>,
+    /// Settings including presets, kept raw so a newer layout can be refused.
+    pub settings: serde_json::Value,
+}
+
+/// How an imported bundle is combined with the local configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Add what's new, keeping local entries whose names collide.
+    Merge,
+    /// Make the local configuration identical to the bundle.
+    Replace,
+}
+
+/// An imported entry whose name is already used locally for something different.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportConflict {
+    /// `filter`, `modifier`, `preset` or `watch_folder`.
+    pub kind: String,
+    pub short_name: String,
+}
+
+/// What importing a bundle changed, or would change on a dry run.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ImportReport {
+    pub filters_added: Vec<String>,
+    pub modifiers_added: Vec<String>,
+    pub presets_added: Vec<String>,
+    /// Paths of watch folders added.
+    pub watch_folders_added: Vec<String>,
+    /// Sections (`defaults`, `limits`, `schedule`) overwritten by a
+    /// replacing import. Merging keeps the local ones.
+    pub sections_replaced: Vec<String>,
+    /// Collisions left as they were locally (merge) or overwritten (replace).
+    pub conflicts: Vec<ImportConflict>,
+    /// Parts of the bundle that couldn't be taken over on this machine.
+    pub warnings: Vec<String>,
+    pub applied: bool,
+}
+
+/// What to do when the output file already exists.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    Overwrite,
+    Skip,
+    #[default]
+    Number,
+}
+
+/// How `tag_original` marks a source as processed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMode {
+    /// Remux the original with a `reprocessed` metadata tag.
+    #[default]
+    File,
+    /// Write `{filename}.reprocessed.json` next to the original.
+    Sidecar,
+    /// Record the original's hash in the processed files database.
+    Database,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -33,4 +180,196 @@ pub struct ProcessingStats {
     pub original_size: u64,
     pub new_size: u64,
     pub output_path: String,
+    pub skipped: bool,
+    /// Source metadata that couldn't be carried over to the output.
+    #[serde(default)]
+    pub metadata_warnings: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PreviewSample {
+    pub sample_path: String,
+    pub sample_size: u64,
+    pub sample_duration_secs: f64,
+    pub source_duration_secs: f64,
+    pub projected_size: u64,
+    pub original_frame: String,
+    pub encoded_frame: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScanEntry {
+    pub path: String,
+    pub name: String,
+    pub status: String,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScanSummary {
+    pub root: String,
+    pub found: usize,
+    pub pending: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    pub skipped_outputs: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WatchFolder {
+    pub path: String,
+    pub options: VideoOptions,
+    pub recursive: bool,
+    pub enabled: bool,
+    pub output_dir: Option<String>,
+    pub archive_dir: Option<String>,
+}
+
+/// Priority and resource caps for every ffmpeg the backend starts. Unset
+/// fields leave the default behaviour.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// `nice` level, -20 to 19 (Linux; on Windows, positive means below normal priority).
+    pub nice: Option<i32>,
+    /// `ionice` class: 1 realtime, 2 best-effort, 3 idle (Linux).
+    pub io_class: Option<u8>,
+    /// `ionice` level 0-7 within the realtime and best-effort classes.
+    pub io_level: Option<u8>,
+    /// Encoder thread cap.
+    pub threads: Option<u32>,
+    /// CPU quota in percent of one core, enforced through `systemd-run --scope` (Linux).
+    pub cpu_quota: Option<u32>,
+}
+
+/// Locations of the ffmpeg and ffprobe binaries; unset ones are looked up on
+/// `PATH` (ffprobe first next to a configured ffmpeg).
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolPaths {
+    pub ffmpeg: Option<String>,
+    pub ffprobe: Option<String>,
+}
+
+/// What running a binary with `-version` revealed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ToolInfo {
+    pub path: String,
+    pub found: bool,
+    pub version: Option<String>,
+    /// `--enable-*` flags from its build configuration.
+    pub configuration: Vec<String>,
+    /// Names from its `-filters` listing, empty when that couldn't be read.
+    pub filters: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Emitted as `ffmpeg-status` after checking the binaries at startup or
+/// when their paths change.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FfmpegInfo {
+    pub ffmpeg: ToolInfo,
+    pub ffprobe: ToolInfo,
+}
+
+/// Emitted as `config-warning` when part of the configuration (a file or
+/// database) couldn't be loaded and defaults are used instead.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConfigWarning {
+    /// File or subsystem the problem is in, e.g. `resource_limits.json`.
+    pub source: String,
+    pub message: String,
+}
+
+/// Emitted as `job-status` when the current job starts, is paused or
+/// resumed (`running`, `paused`) or is being cancelled (`cancelling`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobStatusEvent {
+    pub path: String,
+    pub status: String,
+}
+
+/// Daily hours, `HH:MM` local time. An `end` at or before `start` wraps past
+/// midnight, so `22:00`-`07:00` covers the night.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: String,
+    pub end: String,
+}
+
+/// What happens to a running encode when the schedule closes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleCloseAction {
+    /// Pause it until the schedule opens again.
+    #[default]
+    Pause,
+    /// Let it finish, but start nothing new.
+    FinishFile,
+}
+
+/// When queued encodes may run. Every condition that is set must hold.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    pub enabled: bool,
+    /// Hours during which encodes may run; empty means any time.
+    #[serde(default)]
+    pub windows: Vec<TimeWindow>,
+    /// Only start encodes while the CPU is otherwise mostly idle.
+    #[serde(default)]
+    pub require_idle: bool,
+    /// CPU usage, in percent of all cores, below which the machine counts as idle.
+    pub idle_cpu_percent: Option<f32>,
+    /// Only run while on AC power (machines without a battery always are).
+    #[serde(default)]
+    pub require_ac_power: bool,
+    #[serde(default)]
+    pub on_close: ScheduleCloseAction,
+}
+
+/// Emitted as `schedule-status` whenever the schedule opens or closes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScheduleStatus {
+    pub open: bool,
+    pub reason: Option<String>,
+}
+
+/// Emitted as `queue-event` as files in the batch queue are processed
+/// (`processing`, `done`, `skipped`, `error`, `aborted`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueueEvent {
+    pub path: String,
+    pub status: String,
+    pub message: Option<String>,
+    pub stats: Option<ProcessingStats>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WatchEvent {
+    pub folder: String,
+    pub path: String,
+    pub status: String,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOperation {
+    Delete,
+    Move,
+    Replace,
+    Tag,
+}
+
+/// One file operation performed by the backend, as stored in the journal.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub id: String,
+    pub timestamp: String,
+    pub operation: FileOperation,
+    pub original_path: String,
+    pub new_path: Option<String>,
+    pub backup_path: Option<String>,
+    pub trashed: bool,
+    pub hash: Option<String>,
+    #[serde(default)]
+    pub undone: bool,
 }
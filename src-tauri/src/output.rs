@@ -0,0 +1,281 @@
+use std::path::{Path, PathBuf};
+
+use crate::models::{CollisionPolicy, VideoOptions};
+use crate::probe_format_tags;
+use crate::tools;
+
+/// Template reproducing the classic `{stem}_stabilized_{filters}_{mods}_q{quality}_{codec}` naming.
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{stem}_{stabilized}_{filters}_{modifiers}_q{quality}_{codec}";
+
+/// Extension of every file `process_video` writes.
+pub const OUTPUT_EXTENSION: &str = "mp4";
+
+/// Where an encode should be written and how an existing file there was handled.
+pub struct OutputTarget {
+    pub path: PathBuf,
+    pub action: CollisionAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollisionAction {
+    /// Nothing exists at the path yet.
+    New,
+    /// An existing file will be replaced.
+    Overwrite,
+    /// The preferred name was taken; `path` is the next free numbered name.
+    Renamed { taken: PathBuf },
+    /// An output with the same settings already exists at `path`; don't encode.
+    Skip,
+}
+
+impl OutputTarget {
+    /// Human readable description for the processing log.
+    pub fn describe(&self) -> String {
+        match &self.action {
+            CollisionAction::New => format!("Writing output to {}", self.path.display()),
+            CollisionAction::Overwrite => format!("Output {} exists and will be overwritten", self.path.display()),
+            CollisionAction::Renamed { taken } => format!("Output {} exists, writing to {} instead", taken.display(), self.path.display()),
+            CollisionAction::Skip => format!("Output {} already exists with the same settings, skipping", self.path.display()),
+        }
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '_' | '-' | '.' | ' ')
+}
+
+async fn probe_dimensions(path: &Path) -> Result<(u32, u32), String> {
+    let output = tools::ffprobe_command()
+        .args([
+            "-v", "quiet",
+            "-select_streams", "v:0",
+            "-show_entries", "stream=width,height",
+            "-print_format", "json",
+        ])
+        .arg(path)
+        .output()
+        .await
+        .map_err(tools::ffprobe_spawn_error)?;
+
+    if !output.status.success() {
+        return Err("Failed to run ffprobe".to_string());
+    }
+
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    let stream = metadata.get("streams").and_then(|s| s.get(0)).ok_or("No video stream found")?;
+    let dimension = |key: &str| stream.get(key).and_then(|v| v.as_u64()).map(|v| v as u32);
+    match (dimension("width"), dimension("height")) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err("Could not determine video dimensions".to_string()),
+    }
+}
+
+/// Expands `template` for `input`. Supported tokens: `{stem}`, `{ext}`,
+/// `{codec}`, `{quality}`, `{preset}`, `{filters}`, `{modifiers}`,
+/// `{stabilized}`, `{date}`, `{width}` and `{height}`. A token that expands
+/// to nothing also drops the separator in front of it, so optional parts
+/// don't leave `__` behind.
+pub async fn render_filename(template: &str, input: &Path, options: &VideoOptions) -> Result<String, String> {
+    let stem = input.file_stem().ok_or("Invalid filename")?.to_string_lossy().to_string();
+    let ext = input.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+    let dimensions = if template.contains("{width}") || template.contains("{height}") {
+        Some(probe_dimensions(input).await?)
+    } else {
+        None
+    };
+
+    let mut rendered = String::new();
+    let mut skip_separator = false;
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let literal = &rest[..start];
+        let mut chars = literal.chars();
+        if skip_separator && chars.clone().next().is_some_and(is_separator) {
+            chars.next();
+        }
+        rendered.push_str(chars.as_str());
+        skip_separator = false;
+
+        let end = rest[start..].find('}').ok_or("Unclosed '{' in filename template")? + start;
+        let value = match &rest[start + 1..end] {
+            "stem" => stem.clone(),
+            "ext" => ext.clone(),
+            "codec" => options.codec.clone(),
+            "quality" => options.quality.to_string(),
+            "preset" => options.preset.clone(),
+            "filters" => options.filters.join("_"),
+            "modifiers" => options.modifiers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join("_"),
+            "stabilized" => if options.stabilize { "stabilized".to_string() } else { String::new() },
+            "date" => chrono::Local::now().format("%Y-%m-%d").to_string(),
+            "width" => dimensions.map(|(w, _)| w.to_string()).unwrap_or_default(),
+            "height" => dimensions.map(|(_, h)| h.to_string()).unwrap_or_default(),
+            token => return Err(format!("Unknown filename token {{{}}}", token)),
+        };
+
+        if value.is_empty() {
+            if rendered.ends_with(is_separator) {
+                rendered.pop();
+            } else {
+                skip_separator = true;
+            }
+        } else {
+            rendered.push_str(&value.replace(['/', '\\'], "_"));
+        }
+        rest = &rest[end + 1..];
+    }
+    let mut chars = rest.chars();
+    if skip_separator && chars.clone().next().is_some_and(is_separator) {
+        chars.next();
+    }
+    rendered.push_str(chars.as_str());
+
+    if rendered.is_empty() {
+        return Err("Filename template produced an empty name".to_string());
+    }
+    Ok(rendered)
+}
+
+/// Directory the output for `input` goes to. With `mirror_root`, the part of
+/// the source path below it is recreated under `output_dir`.
+pub fn output_directory(input: &Path, options: &VideoOptions) -> Result<PathBuf, String> {
+    let parent = input.parent().ok_or("Invalid input path")?;
+    let Some(output_dir) = &options.output_dir else {
+        return Ok(parent.to_path_buf());
+    };
+
+    let output_dir = PathBuf::from(output_dir);
+    match options.mirror_root.as_ref().and_then(|root| parent.strip_prefix(root).ok()) {
+        Some(relative) => Ok(output_dir.join(relative)),
+        None => Ok(output_dir),
+    }
+}
+
+/// First `{stem}_{n}.{ext}` next to `path` that doesn't exist yet.
+pub fn numbered_path(path: &Path) -> Result<PathBuf, String> {
+    let dir = path.parent().ok_or("Invalid output path")?;
+    let stem = path.file_stem().ok_or("Invalid output path")?.to_string_lossy();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| dir.join(format!("{}_{}{}", stem, n, ext)))
+        .find(|candidate| !candidate.exists())
+        .ok_or_else(|| "No free output filename".to_string())
+}
+
+/// True if `output` names the file at `input`, even when spelled through a
+/// different path to the same directory.
+fn is_source(output: &Path, input: &Path) -> bool {
+    if output == input {
+        return true;
+    }
+    match (std::fs::canonicalize(output), std::fs::canonicalize(input)) {
+        (Ok(output), Ok(input)) => output == input,
+        _ => false,
+    }
+}
+
+/// Works out the final output path for `input` and applies the collision
+/// policy. `skip` only applies when the existing file carries the same
+/// `reprocessed` tag we are about to write; otherwise it falls back to a
+/// numbered name so a different encode is never lost. An output that would
+/// land on the source itself is always numbered.
+pub async fn resolve_output(input: &Path, options: &VideoOptions, reprocessed_tag: &str) -> Result<OutputTarget, String> {
+    let template = options.filename_template.as_deref().unwrap_or(DEFAULT_FILENAME_TEMPLATE);
+    let name = render_filename(template, input, options).await?;
+    let dir = output_directory(input, options)?;
+    let path = dir.join(format!("{}.{}", name, OUTPUT_EXTENSION));
+
+    if is_source(&path, input) {
+        if !options.replace_original {
+            return Err(format!("Filename template \"{}\" names the output after the source itself; add a token such as {{quality}} or set an output directory", template));
+        }
+        // The encode still reads the source; the output only takes its
+        // name once `replace_original` has set it aside
+        return Ok(OutputTarget { path: numbered_path(&path)?, action: CollisionAction::Renamed { taken: path } });
+    }
+
+    if !path.exists() {
+        return Ok(OutputTarget { path, action: CollisionAction::New });
+    }
+
+    match options.on_collision {
+        CollisionPolicy::Overwrite => Ok(OutputTarget { path, action: CollisionAction::Overwrite }),
+        CollisionPolicy::Skip => {
+            let tags = probe_format_tags(&path.to_string_lossy()).await.unwrap_or_default();
+            if tags.get("reprocessed").and_then(|t| t.as_str()) == Some(reprocessed_tag) {
+                Ok(OutputTarget { path, action: CollisionAction::Skip })
+            } else {
+                Ok(OutputTarget { path: numbered_path(&path)?, action: CollisionAction::Renamed { taken: path } })
+            }
+        }
+        CollisionPolicy::Number => Ok(OutputTarget { path: numbered_path(&path)?, action: CollisionAction::Renamed { taken: path } }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> VideoOptions {
+        VideoOptions {
+            filters: vec!["denoise".to_string(), "sharpen".to_string()],
+            modifiers: vec![("fast".to_string(), "-preset veryfast".to_string())],
+            quality: 23,
+            codec: "libx264".to_string(),
+            ..VideoOptions::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn default_template_matches_classic_naming() {
+        let mut options = options();
+        options.stabilize = true;
+        let name = render_filename(DEFAULT_FILENAME_TEMPLATE, Path::new("/videos/holiday.mkv"), &options).await.unwrap();
+        assert_eq!(name, "holiday_stabilized_denoise_sharpen_fast_q23_libx264");
+    }
+
+    #[tokio::test]
+    async fn empty_tokens_drop_their_separator() {
+        let options = VideoOptions { filters: Vec::new(), modifiers: Vec::new(), ..options() };
+        let name = render_filename(DEFAULT_FILENAME_TEMPLATE, Path::new("/videos/holiday.mkv"), &options).await.unwrap();
+        assert_eq!(name, "holiday_q23_libx264");
+
+        let name = render_filename("{stabilized}-{stem}", Path::new("/videos/holiday.mkv"), &options).await.unwrap();
+        assert_eq!(name, "holiday");
+    }
+
+    #[tokio::test]
+    async fn unknown_tokens_are_rejected() {
+        let result = render_filename("{stem}_{bitrate}", Path::new("/videos/holiday.mkv"), &options()).await;
+        assert_eq!(result, Err("Unknown filename token {bitrate}".to_string()));
+
+        let result = render_filename("{stem", Path::new("/videos/holiday.mkv"), &options()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn output_named_after_source_is_rejected() {
+        let options = VideoOptions { filename_template: Some("{stem}".to_string()), ..options() };
+        let result = resolve_output(Path::new("/videos/holiday.mp4"), &options, "tag").await;
+        assert!(result.is_err());
+
+        // A different extension doesn't collide with the source
+        let target = resolve_output(Path::new("/nonexistent/holiday.mkv"), &options, "tag").await.unwrap();
+        assert_eq!(target.path, PathBuf::from("/nonexistent/holiday.mp4"));
+    }
+
+    #[tokio::test]
+    async fn output_named_after_source_is_numbered_when_replacing() {
+        let dir = std::env::temp_dir().join(format!("output_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("holiday.mp4");
+        std::fs::write(&input, b"video").unwrap();
+
+        let options = VideoOptions { filename_template: Some("{stem}".to_string()), replace_original: true, on_collision: CollisionPolicy::Overwrite, ..options() };
+        let target = resolve_output(&input, &options, "tag").await.unwrap();
+        assert_eq!(target.path, dir.join("holiday_1.mp4"));
+        assert_eq!(target.action, CollisionAction::Renamed { taken: input });
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
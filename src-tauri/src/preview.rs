@@ -0,0 +1,284 @@
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::limits;
+use crate::models::{PreviewSample, VideoOptions};
+use crate::scratch;
+use crate::thumbnails::{thumbnail_url, ThumbnailCache};
+use crate::tools;
+use crate::{build_filter_chain, encoding_args, escape_filter_path, probe_duration};
+
+/// Height both halves of a comparison preview are scaled to.
+const COMPARE_HEIGHT: u32 = 480;
+
+/// Width of a single tile in a contact sheet.
+const CONTACT_TILE_WIDTH: u32 = 320;
+
+/// Runs ffmpeg with `args` and returns the single JPEG it writes to stdout.
+async fn render_jpeg(mut args: Vec<String>) -> Result<Vec<u8>, String> {
+    args.extend([
+        "-f".to_string(), "image2pipe".to_string(),
+        "-c:v".to_string(), "mjpeg".to_string(),
+        "pipe:1".to_string(),
+    ]);
+
+    let output = limits::ffmpeg_command(&args)
+        .output()
+        .await
+        .map_err(tools::ffmpeg_spawn_error)?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(format!("Failed to generate preview: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Grabs a single frame at `seek_secs`, optionally through filter arguments
+/// (`-vf ...` or `-filter_complex ... -map ...`).
+async fn extract_frame(path: &Path, seek_secs: f64, filter_args: &[String]) -> Result<Vec<u8>, String> {
+    let mut args = vec![
+        "-ss".to_string(), format!("{:.3}", seek_secs),
+        "-i".to_string(), path.to_string_lossy().to_string(),
+    ];
+    args.extend(filter_args.iter().cloned());
+    args.extend([
+        "-vframes".to_string(), "1".to_string(),
+        "-q:v".to_string(), "2".to_string(),
+    ]);
+
+    render_jpeg(args).await
+}
+
+/// Returns the thumbnail URL for a frame of `path`, extracting and caching
+/// it first if needed.
+async fn cached_frame(cache: &ThumbnailCache, path: &Path, seek_secs: f64, filter_args: &[String]) -> Result<String, String> {
+    let variant = format!("frame@{:.3}|{}", seek_secs, filter_args.join(" "));
+    let key = ThumbnailCache::key(path, &variant)?;
+
+    if !cache.contains(&key) {
+        let image = extract_frame(path, seek_secs, filter_args).await?;
+        cache.insert(&key, &image)?;
+    }
+
+    Ok(thumbnail_url(&key))
+}
+
+/// Caches a frame of the preview sample `sample` under a key derived from
+/// its `source` and `variant` rather than the short-lived sample itself, so
+/// previewing the same settings again reuses the entry.
+async fn cached_sample_frame(cache: &ThumbnailCache, source: &Path, variant: &str, sample: &Path, seek_secs: f64) -> Result<String, String> {
+    let key = ThumbnailCache::key(source, &format!("sample@{:.3}|{}", seek_secs, variant))?;
+    if !cache.contains(&key) {
+        let image = extract_frame(sample, seek_secs, &[]).await?;
+        cache.insert(&key, &image)?;
+    }
+    Ok(thumbnail_url(&key))
+}
+
+/// Builds a filtergraph that places the original frame next to the
+/// processed one. `split` shows the left half of the original against the
+/// right half of the processed frame; anything else is side by side.
+fn comparison_graph(filters: &[String], layout: &str) -> String {
+    let processed = if filters.is_empty() {
+        "null".to_string()
+    } else {
+        filters.join(",")
+    };
+
+    let (left, right) = if layout == "split" {
+        ("crop=iw/2:ih:0:0", "crop=iw/2:ih:iw/2:0")
+    } else {
+        ("null", "null")
+    };
+
+    format!(
+        "[0:v]split=2[orig][proc];[proc]{processed},scale=-2:{h}[p];[orig]scale=-2:{h}[o];[o]{left}[l];[p]{right}[r];[l][r]hstack=inputs=2[out]",
+        processed = processed,
+        h = COMPARE_HEIGHT,
+        left = left,
+        right = right,
+    )
+}
+
+/// Returns a still from `path`. The frame is taken at `timestamp` seconds or
+/// at `percent` of the duration (default: first frame). When `options` are
+/// given, the filters and modifiers `process_video` would apply are rendered
+/// next to the original according to `layout` (`side_by_side` or `split`).
+/// Stabilization needs motion analysis and is not shown.
+#[tauri::command]
+pub async fn generate_preview(app: AppHandle, path: String, timestamp: Option<f64>, percent: Option<f64>, options: Option<VideoOptions>, layout: Option<String>) -> Result<String, String> {
+    let seek_secs = match (timestamp, percent) {
+        (Some(timestamp), _) => timestamp.max(0.0),
+        (None, Some(percent)) => {
+            let duration = probe_duration(&path).await?;
+            duration * percent.clamp(0.0, 100.0) / 100.0
+        }
+        (None, None) => 0.0,
+    };
+
+    let filter_args = match &options {
+        Some(options) => {
+            let chain = build_filter_chain(&app, options, None)?;
+            vec![
+                "-filter_complex".to_string(),
+                comparison_graph(&chain.filters, layout.as_deref().unwrap_or("side_by_side")),
+                "-map".to_string(), "[out]".to_string(),
+            ]
+        }
+        None => Vec::new(),
+    };
+
+    let cache = app.state::<ThumbnailCache>();
+    cached_frame(&cache, Path::new(&path), seek_secs, &filter_args).await
+}
+
+/// Encodes a short segment of `path` with the full `options` so quality and
+/// size can be judged before committing to a batch. The sample stays in the
+/// preview dir until the next startup's orphan cleanup.
+#[tauri::command]
+pub async fn preview_encode(app: AppHandle, path: String, options: VideoOptions, start_secs: f64, duration_secs: f64) -> Result<PreviewSample, String> {
+    if duration_secs <= 0.0 {
+        return Err("Preview duration must be positive".to_string());
+    }
+    tools::check_capabilities(&options)?;
+
+    let input_path_buf = PathBuf::from(&path);
+    let stem = input_path_buf.file_stem().ok_or("Invalid filename")?.to_string_lossy();
+    let source_duration = probe_duration(&path).await?;
+    let start_secs = start_secs.clamp(0.0, source_duration);
+
+    let temp_dir = scratch::preview_dir(&app, &options)?;
+    let sample = scratch::TempFile::new(temp_dir.join(format!("{}_{}_preview.mp4", stem, uuid::Uuid::new_v4())));
+    let sample_path = sample.path();
+    let segment = [
+        "-ss".to_string(), format!("{:.3}", start_secs),
+        "-i".to_string(), path.clone(),
+        "-t".to_string(), format!("{:.3}", duration_secs),
+    ];
+
+    // Stabilization needs its own detection pass over the same segment
+    let trf_file = if options.stabilize {
+        let trf_file = scratch::TempFile::new(temp_dir.join(format!("{}_{}_preview.trf", stem, uuid::Uuid::new_v4())));
+        let mut args_pass1 = vec!["-y".to_string()];
+        args_pass1.extend(segment.iter().cloned());
+        args_pass1.extend([
+            "-vf".to_string(),
+            format!("vidstabdetect=stepsize=32:shakiness=10:accuracy=15:result='{}'", escape_filter_path(trf_file.path())),
+            "-f".to_string(), "null".to_string(), "-".to_string(),
+        ]);
+
+        let output = limits::ffmpeg_command(&args_pass1)
+            .output()
+            .await
+            .map_err(tools::ffmpeg_spawn_error)?;
+
+        if !output.status.success() {
+            return Err(format!("Preview stabilization pass failed: {}", String::from_utf8_lossy(&output.stderr)));
+        }
+        Some(trf_file)
+    } else {
+        None
+    };
+
+    let chain = build_filter_chain(&app, &options, trf_file.as_ref().map(|trf| trf.path()));
+
+    let encoding = chain.map(|chain| encoding_args(&options, &chain, false));
+    let result = match &encoding {
+        Ok(encoding) => {
+            let mut args = vec!["-y".to_string()];
+            if options.hwaccel != "none" {
+                args.push("-hwaccel".to_string());
+                args.push(options.hwaccel.clone());
+            }
+            args.extend(segment.iter().cloned());
+            args.push("-c:a".to_string()); args.push("copy".to_string());
+            args.extend(encoding.iter().cloned());
+            args.push(sample_path.to_string_lossy().to_string());
+
+            limits::ffmpeg_command(&args)
+                .output()
+                .await
+                .map_err(tools::ffmpeg_spawn_error)
+        }
+        Err(e) => Err(e.clone()),
+    };
+
+    drop(trf_file);
+
+    let output = result?;
+    if !output.status.success() {
+        return Err(format!("Preview encode failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    let sample_size = std::fs::metadata(sample_path).map_err(|e| e.to_string())?.len();
+    let sample_duration = probe_duration(&sample_path.to_string_lossy()).await?;
+
+    // Extrapolate from the sample bitrate over the whole source
+    let projected_size = if sample_duration > 0.0 {
+        (sample_size as f64 / sample_duration * source_duration) as u64
+    } else {
+        0
+    };
+
+    let cache = app.state::<ThumbnailCache>();
+    let middle = sample_duration / 2.0;
+    let original_frame = cached_frame(&cache, &input_path_buf, start_secs + middle, &[]).await?;
+    let variant = format!("{:.3}+{:.3}|{}", start_secs, duration_secs, encoding?.join(" "));
+    let encoded_frame = cached_sample_frame(&cache, &input_path_buf, &variant, sample_path, middle).await?;
+
+    Ok(PreviewSample {
+        sample_path: sample.keep().to_string_lossy().to_string(),
+        sample_size,
+        sample_duration_secs: sample_duration,
+        source_duration_secs: source_duration,
+        projected_size,
+        original_frame,
+        encoded_frame,
+    })
+}
+
+/// Builds a `columns`×`rows` grid of frames at evenly spaced timestamps, each
+/// labelled with its timecode when ffmpeg has `drawtext` (it needs
+/// libfreetype). Only keyframes are decoded, so tiles snap to
+/// the first keyframe after each interval. Sheets are cached in the app cache
+/// thumbnail cache and reused until the file changes.
+#[tauri::command]
+pub async fn generate_contact_sheet(app: AppHandle, path: String, columns: Option<u32>, rows: Option<u32>) -> Result<String, String> {
+    let columns = columns.unwrap_or(4).max(1);
+    let rows = rows.unwrap_or(4).max(1);
+    let input_path = PathBuf::from(&path);
+
+    let cache = app.state::<ThumbnailCache>();
+    let labels = tools::has_filter("drawtext");
+    let key = ThumbnailCache::key(&input_path, &format!("sheet{}x{}{}", columns, rows, if labels { "" } else { "-plain" }))?;
+
+    if cache.contains(&key) {
+        return Ok(thumbnail_url(&key));
+    }
+
+    let duration = probe_duration(&path).await?;
+    let interval = duration / f64::from(columns * rows);
+
+    let mut filters = vec![
+        format!("select='isnan(prev_selected_t)+gte(t-prev_selected_t,{:.3})'", interval),
+        format!("scale={}:-2", CONTACT_TILE_WIDTH),
+    ];
+    if labels {
+        filters.push("drawtext=text='%{pts\\:hms}':x=5:y=h-th-5:fontsize=18:fontcolor=white:box=1:boxcolor=black@0.5".to_string());
+    }
+    filters.push(format!("tile={}x{}", columns, rows));
+
+    let args = vec![
+        "-skip_frame".to_string(), "nokey".to_string(),
+        "-i".to_string(), path.clone(),
+        "-vf".to_string(), filters.join(","),
+        "-vsync".to_string(), "vfr".to_string(),
+        "-frames:v".to_string(), "1".to_string(),
+        "-q:v".to_string(), "3".to_string(),
+    ];
+
+    let image = render_jpeg(args).await?;
+    cache.insert(&key, &image)?;
+    Ok(thumbnail_url(&key))
+}
@@ -0,0 +1,261 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::hashing::partial_hash;
+use crate::limits;
+use crate::models::{FileOperation, ProcessingStats, TagMode, VideoOptions};
+use crate::metadata::{replace_in_place, FileMetadata};
+use crate::{tools, warnings};
+use crate::{journal, LogPayload};
+
+const DATABASE_FILE: &str = "processed.sqlite";
+
+/// Suffix appended to the original's full filename for sidecar records.
+const SIDECAR_SUFFIX: &str = ".reprocessed.json";
+
+/// Central record of encoded inputs (options, output and stats), keyed by
+/// partial content hash so it still recognises files after they are renamed,
+/// copied or stripped of their tags.
+pub struct ProcessedDb {
+    conn: Mutex<Connection>,
+}
+
+impl ProcessedDb {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS processed_files (
+                hash TEXT PRIMARY KEY,
+                size INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                reprocessed TEXT NOT NULL,
+                processed_at TEXT NOT NULL,
+                options TEXT,
+                output_path TEXT,
+                duration_secs REAL,
+                original_size INTEGER,
+                new_size INTEGER,
+                marked INTEGER NOT NULL DEFAULT 1
+            );
+            CREATE INDEX IF NOT EXISTS processed_files_size ON processed_files(size);
+            CREATE INDEX IF NOT EXISTS processed_files_output ON processed_files(output_path);",
+        ).map_err(|e| e.to_string())?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Stores the outcome of encoding the input identified by `fingerprint`.
+    pub fn record(&self, input: &Path, fingerprint: &Fingerprint, options: &VideoOptions, reprocessed_tag: &str, stats: &ProcessingStats) -> Result<(), String> {
+        let options_json = serde_json::to_string(options).map_err(|e| e.to_string())?;
+        let conn = self.conn.lock().map_err(|_| "Failed to lock database")?;
+        conn.execute(
+            "INSERT OR REPLACE INTO processed_files
+                (hash, size, path, reprocessed, processed_at, options, output_path, duration_secs, original_size, new_size, marked)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                fingerprint.hash,
+                fingerprint.size as i64,
+                input.to_string_lossy(),
+                reprocessed_tag,
+                chrono::Local::now().to_rfc3339(),
+                options_json,
+                stats.output_path,
+                stats.duration_secs,
+                stats.original_size as i64,
+                stats.new_size as i64,
+                options.tag_original,
+            ],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// True if a file with the same content was recorded and marked as
+    /// processed. Only hashes when some record has the same size, so
+    /// checking unrelated files stays cheap.
+    pub fn contains(&self, path: &Path) -> Result<bool, String> {
+        let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+        {
+            let conn = self.conn.lock().map_err(|_| "Failed to lock database")?;
+            let candidate: Option<i64> = conn
+                .query_row("SELECT 1 FROM processed_files WHERE size = ?1 AND marked = 1 LIMIT 1", params![size as i64], |row| row.get(0))
+                .optional()
+                .map_err(|e| e.to_string())?;
+            if candidate.is_none() {
+                return Ok(false);
+            }
+        }
+
+        let hash = partial_hash(path)?;
+        let conn = self.conn.lock().map_err(|_| "Failed to lock database")?;
+        let found: Option<i64> = conn
+            .query_row("SELECT 1 FROM processed_files WHERE hash = ?1 AND marked = 1", params![hash], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?;
+        Ok(found.is_some())
+    }
+
+    /// True if `path` was written as the output of a recorded encode.
+    pub fn is_output(&self, path: &Path) -> Result<bool, String> {
+        let conn = self.conn.lock().map_err(|_| "Failed to lock database")?;
+        let found: Option<i64> = conn
+            .query_row("SELECT 1 FROM processed_files WHERE output_path = ?1 LIMIT 1", params![path.to_string_lossy()], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?;
+        Ok(found.is_some())
+    }
+}
+
+/// Size and partial hash identifying an input independent of its path.
+pub struct Fingerprint {
+    pub size: u64,
+    pub hash: String,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path) -> Result<Self, String> {
+        let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+        Ok(Self { size, hash: partial_hash(path)? })
+    }
+}
+
+/// Records a finished encode in the processed files database, logging
+/// rather than failing when that isn't possible.
+pub fn record_encode(app: &AppHandle, input: &Path, fingerprint: Result<Fingerprint, String>, options: &VideoOptions, reprocessed_tag: &str, stats: &ProcessingStats) {
+    let result = fingerprint.and_then(|fingerprint| match app.try_state::<ProcessedDb>() {
+        Some(db) => db.record(input, &fingerprint, options, reprocessed_tag, stats),
+        None => Err("Processed files database is not available".to_string()),
+    });
+    if let Err(e) = result {
+        let _ = app.emit("processing-log", LogPayload { path: input.to_string_lossy().to_string(), message: format!("Failed to record encode in processed files database: {}", e) });
+    }
+}
+
+/// `clip.mkv` -> `clip.mkv.reprocessed.json`
+pub fn sidecar_path(input: &Path) -> PathBuf {
+    let mut name = input.file_name().unwrap_or_default().to_os_string();
+    name.push(SIDECAR_SUFFIX);
+    input.with_file_name(name)
+}
+
+fn write_sidecar(input: &Path, reprocessed_tag: &str, output: &Path) -> Result<PathBuf, String> {
+    let path = sidecar_path(input);
+    let content = serde_json::json!({
+        "reprocessed": reprocessed_tag,
+        "output_path": output.to_string_lossy(),
+        "hash": partial_hash(input).ok(),
+        "processed_at": chrono::Local::now().to_rfc3339(),
+    });
+    let content = serde_json::to_string_pretty(&content).map_err(|e| e.to_string())?;
+    std::fs::write(&path, content).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Remuxes the original with a `reprocessed` tag and swaps it in place,
+/// keeping its container, timestamps and other filesystem metadata.
+async fn tag_in_file(app: &AppHandle, input: &Path) -> Result<(), String> {
+    let parent = input.parent().ok_or("Invalid input path")?;
+    let stem = input.file_stem().ok_or("Invalid filename")?.to_string_lossy();
+    let ext = input.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_else(|| "mp4".to_string());
+    let temp_tag_path = parent.join(format!("{}_{}_tagged_temp.{}", stem, uuid::Uuid::new_v4(), ext));
+
+    // Before remuxing, which reads the file and so may bump its atime
+    let original = FileMetadata::capture(input)?;
+
+    let tag_args = vec![
+        "-n".to_string(),
+        "-i".to_string(), input.to_string_lossy().to_string(),
+        "-c".to_string(), "copy".to_string(),
+        "-map_metadata".to_string(), "0".to_string(),
+        "-metadata".to_string(), "reprocessed=tagged_as_processed".to_string(),
+        temp_tag_path.to_string_lossy().to_string()
+    ];
+
+    let tag_output = limits::ffmpeg_command(&tag_args)
+        .output()
+        .await
+        .map_err(tools::ffmpeg_spawn_error)?;
+
+    if !tag_output.status.success() {
+        let _ = std::fs::remove_file(&temp_tag_path); // Cleanup
+        return Err(format!("Failed to tag original file: {}", String::from_utf8_lossy(&tag_output.stderr)));
+    }
+
+    // Replace original with tagged
+    let entry = journal::new_entry(FileOperation::Tag, input);
+    let problems = match replace_in_place(input, &temp_tag_path, &original) {
+        Ok(problems) => problems,
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp_tag_path); // Cleanup
+            return Err(format!("Failed to replace original file with tagged version: {}", e));
+        }
+    };
+    let log = |message: String| {
+        let _ = app.emit("processing-log", LogPayload { path: input.to_string_lossy().to_string(), message });
+    };
+    // The original has been rewritten either way, so a journal failure doesn't fail the tagging
+    if let Err(e) = journal::record(app, &entry) {
+        log(format!("Original tagged, but failed to record it in journal: {}", e));
+    }
+    for problem in problems {
+        log(format!("Could not restore {} of the tagged original", problem));
+    }
+    Ok(())
+}
+
+/// Marks `input` as processed according to `options.tag_mode`, logging the
+/// outcome. Failures are logged rather than failing the finished encode.
+pub async fn mark_processed(app: &AppHandle, input: &Path, options: &VideoOptions, reprocessed_tag: &str, output: &Path) {
+    let log = |message: String| {
+        let _ = app.emit("processing-log", LogPayload { path: input.to_string_lossy().to_string(), message });
+    };
+
+    let result = match options.tag_mode {
+        TagMode::File => {
+            log("Tagging original file...".to_string());
+            tag_in_file(app, input).await.map(|_| "Original file successfully tagged.".to_string())
+        }
+        TagMode::Sidecar => write_sidecar(input, reprocessed_tag, output)
+            .map(|path| format!("Recorded original as processed in {}", path.display())),
+        // The encode itself is recorded by `record_encode`
+        TagMode::Database => Ok("Original left untouched, marked as processed in database.".to_string()),
+    };
+
+    match result {
+        Ok(message) => log(message),
+        Err(e) => log(e),
+    }
+}
+
+/// True if a sidecar or database record marks `path` as already processed.
+pub fn is_recorded(app: &AppHandle, path: &Path) -> bool {
+    if sidecar_path(path).exists() {
+        return true;
+    }
+    app.try_state::<ProcessedDb>()
+        .is_some_and(|db| db.contains(path).unwrap_or(false))
+}
+
+/// True if the processed files database lists `path` as an encode's output,
+/// which catches outputs named by a custom template.
+pub fn is_recorded_output(app: &AppHandle, path: &Path) -> bool {
+    app.try_state::<ProcessedDb>()
+        .is_some_and(|db| db.is_output(path).unwrap_or(false))
+}
+
+/// Opens the processed files database in the app data dir. Without it,
+/// encodes still run but aren't recorded, which is reported as a warning.
+pub fn init(app: &AppHandle) {
+    let opened = app.path().app_data_dir()
+        .map_err(|e| e.to_string())
+        .and_then(|data_dir| {
+            std::fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
+            ProcessedDb::open(&data_dir.join(DATABASE_FILE))
+        });
+    match opened {
+        Ok(db) => {
+            app.manage(db);
+        }
+        Err(e) => warnings::report(app, DATABASE_FILE, format!("Processed files database unavailable, encodes won't be recorded: {}", e)),
+    }
+}
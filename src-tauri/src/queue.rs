@@ -0,0 +1,116 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+use crate::models::{ProcessingStats, QueueEvent, VideoOptions};
+use crate::{encode_file, job, schedule, AppState};
+
+struct QueueItem {
+    path: String,
+    options: VideoOptions,
+}
+
+/// Files waiting to be encoded one after another by the backend, so a
+/// batch keeps going (and follows the schedule) without the UI driving it.
+pub struct QueueState {
+    items: Mutex<VecDeque<QueueItem>>,
+    added: Notify,
+    /// True while the worker is encoding a file.
+    busy: AtomicBool,
+}
+
+fn emit_queue_event(app: &AppHandle, path: &str, status: &str, message: Option<String>, stats: Option<ProcessingStats>) {
+    let _ = app.emit("queue-event", QueueEvent {
+        path: path.to_string(),
+        status: status.to_string(),
+        message,
+        stats,
+    });
+}
+
+/// Waits for a file to be queued and for the schedule to allow encoding it.
+async fn next_item(app: &AppHandle) -> QueueItem {
+    let state = app.state::<QueueState>();
+    loop {
+        let has_items = state.items.lock().map(|items| !items.is_empty()).unwrap_or(false);
+        if !has_items {
+            state.added.notified().await;
+            continue;
+        }
+        schedule::wait_until_open(app).await;
+        // The queue may have been cleared while waiting
+        if let Some(item) = state.items.lock().ok().and_then(|mut items| items.pop_front()) {
+            return item;
+        }
+    }
+}
+
+async fn process_item(app: &AppHandle, item: QueueItem) {
+    emit_queue_event(app, &item.path, "processing", None, None);
+    let state = app.state::<AppState>();
+    match encode_file(app, &state, item.path.clone(), item.options).await {
+        Ok(stats) => {
+            let status = if stats.skipped { "skipped" } else { "done" };
+            emit_queue_event(app, &item.path, status, None, Some(stats));
+        }
+        Err(e) if e == job::CANCELLED => emit_queue_event(app, &item.path, "aborted", Some(e), None),
+        Err(e) => emit_queue_event(app, &item.path, "error", Some(e), None),
+    }
+}
+
+async fn run_worker(app: AppHandle) {
+    let state = app.state::<QueueState>();
+    loop {
+        let item = next_item(&app).await;
+        state.busy.store(true, Ordering::SeqCst);
+        process_item(&app, item).await;
+        state.busy.store(false, Ordering::SeqCst);
+
+        if state.items.lock().map(|items| items.is_empty()).unwrap_or(true) {
+            let _ = app.emit("queue-idle", ());
+        }
+    }
+}
+
+/// Sets up the batch queue and starts its worker.
+pub fn init(app: &AppHandle) {
+    app.manage(QueueState {
+        items: Mutex::new(VecDeque::new()),
+        added: Notify::new(),
+        busy: AtomicBool::new(false),
+    });
+    tauri::async_runtime::spawn(run_worker(app.clone()));
+}
+
+/// Appends `paths` to the queue, to be encoded with `options`. Progress is
+/// reported through `queue-event`, and `queue-idle` once the queue is empty.
+#[tauri::command]
+pub fn enqueue_files(state: State<QueueState>, paths: Vec<String>, options: VideoOptions) -> Result<(), String> {
+    let mut items = state.items.lock().map_err(|_| "Failed to lock queue")?;
+    items.extend(paths.into_iter().map(|path| QueueItem { path, options: options.clone() }));
+    state.added.notify_one();
+    Ok(())
+}
+
+/// Removes every file that hasn't started yet, returning their paths. The
+/// running encode is left alone; `cancel_processing` stops it.
+#[tauri::command]
+pub fn clear_queue(app: AppHandle, state: State<QueueState>) -> Result<Vec<String>, String> {
+    let removed: Vec<String> = state.items.lock()
+        .map_err(|_| "Failed to lock queue")?
+        .drain(..)
+        .map(|item| item.path)
+        .collect();
+    if !state.busy.load(Ordering::SeqCst) {
+        let _ = app.emit("queue-idle", ());
+    }
+    Ok(removed)
+}
+
+#[tauri::command]
+pub fn get_queue(state: State<QueueState>) -> Result<Vec<String>, String> {
+    let items = state.items.lock().map_err(|_| "Failed to lock queue")?;
+    Ok(items.iter().map(|item| item.path.clone()).collect())
+}
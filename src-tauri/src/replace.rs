@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+use crate::metadata::FileMetadata;
+use crate::models::{FileOperation, VideoOptions};
+use crate::output::{numbered_path, OUTPUT_EXTENSION};
+use crate::{journal, move_file, probe_duration, LogPayload};
+
+/// Allowed difference between source and output duration before the output
+/// is considered truncated.
+const DURATION_TOLERANCE_SECS: f64 = 1.0;
+
+/// Checks that the encode is a complete, readable video before the original
+/// is given up. Durations are only compared when no modifiers were used,
+/// since those may legitimately trim the clip.
+async fn verify_output(input: &Path, output: &Path, options: &VideoOptions) -> Result<(), String> {
+    let size = std::fs::metadata(output).map_err(|e| e.to_string())?.len();
+    if size == 0 {
+        return Err("Output is empty".to_string());
+    }
+
+    let output_duration = probe_duration(&output.to_string_lossy()).await?;
+    if output_duration <= 0.0 {
+        return Err("Output has no playable duration".to_string());
+    }
+
+    if options.modifiers.is_empty() {
+        let input_duration = probe_duration(&input.to_string_lossy()).await?;
+        let tolerance = (input_duration * 0.02).max(DURATION_TOLERANCE_SECS);
+        if (input_duration - output_duration).abs() > tolerance {
+            return Err(format!("Output duration {:.1}s doesn't match source {:.1}s", output_duration, input_duration));
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the original to `backup_dir`, or to the OS trash when none is set.
+/// Returns the backup location (`None` for the trash).
+fn set_aside_original(input: &Path, backup_dir: Option<&str>) -> Result<Option<PathBuf>, String> {
+    match backup_dir {
+        Some(backup_dir) => {
+            std::fs::create_dir_all(backup_dir).map_err(|e| e.to_string())?;
+            let mut backup_path = Path::new(backup_dir).join(input.file_name().ok_or("Invalid filename")?);
+            if backup_path.exists() {
+                backup_path = numbered_path(&backup_path)?;
+            }
+            move_file(input, &backup_path)?;
+            Ok(Some(backup_path))
+        }
+        None => {
+            trash::delete(input).map_err(|e| format!("Failed to move original to trash: {}", e))?;
+            Ok(None)
+        }
+    }
+}
+
+/// Moves `output` to the set-aside original's name (with an `.mp4`
+/// extension), or a numbered one if another file already has it.
+fn take_name(input: &Path, output: &Path) -> Result<PathBuf, String> {
+    let mut replacement = input.with_extension(OUTPUT_EXTENSION);
+    if replacement.exists() && replacement != output {
+        replacement = numbered_path(&replacement)?;
+    }
+    move_file(output, &replacement)?;
+    Ok(replacement)
+}
+
+/// Replaces `input` with the verified `output`: the original goes to the
+/// trash or backup folder, the output takes its name (with an `.mp4`
+/// extension), timestamps, permissions and attributes. The swap is journaled
+/// as soon as the original is set aside, so it can be undone even if the
+/// rename fails; in that case the original is moved back straight away.
+/// Returns the output's new path.
+pub async fn replace_original(app: &AppHandle, input: &Path, output: &Path, options: &VideoOptions) -> Result<PathBuf, String> {
+    let log = |message: String| {
+        let _ = app.emit("processing-log", LogPayload { path: input.to_string_lossy().to_string(), message });
+    };
+
+    verify_output(input, output, options).await
+        .map_err(|e| format!("Not replacing original, output failed verification: {}", e))?;
+
+    let metadata = FileMetadata::capture(input)?;
+    let mut entry = journal::new_entry(FileOperation::Replace, input);
+
+    let backup_path = set_aside_original(input, options.backup_dir.as_deref())?;
+    match &backup_path {
+        Some(path) => log(format!("Original moved to {}", path.display())),
+        None => log("Original moved to trash".to_string()),
+    }
+    entry.backup_path = backup_path.map(|p| p.to_string_lossy().to_string());
+    entry.trashed = options.backup_dir.is_none();
+    if let Err(e) = journal::record(app, &entry) {
+        log(format!("Failed to record replacement in journal: {}", e));
+    }
+
+    let replacement = match take_name(input, output) {
+        Ok(replacement) => replacement,
+        Err(e) => {
+            return Err(match journal::revert(app, &mut entry) {
+                Ok(()) => format!("Failed to rename output, original restored: {}", e),
+                Err(restore_error) => format!("Failed to rename output: {}. The original could not be restored: {}", e, restore_error),
+            });
+        }
+    };
+
+    for problem in metadata.apply(&replacement) {
+        log(format!("Could not preserve {} of the original", problem));
+    }
+    log(format!("Output renamed to {}", replacement.display()));
+
+    entry.new_path = Some(replacement.to_string_lossy().to_string());
+    if let Err(e) = journal::update(app, &entry) {
+        log(format!("Failed to record renamed output in journal: {}", e));
+    }
+
+    Ok(replacement)
+}
@@ -0,0 +1,206 @@
+use std::path::{Path, PathBuf};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use tauri::{AppHandle, Emitter, Manager, Window};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::{check_file_status, processed, tools};
+use crate::segments::SEGMENT_DIR_SUFFIX;
+use crate::models::{ScanEntry, ScanSummary};
+
+/// Extensions picked up when scanning folders.
+pub const VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "mkv", "avi", "mov", "webm", "m4v", "mts", "m2ts", "mpg", "mpeg", "wmv", "flv", "3gp", "ts",
+];
+
+/// Number of files sent per `scan-files` event.
+const SCAN_BATCH_SIZE: usize = 100;
+
+/// Number of ffprobe status checks run at once.
+const SCAN_CONCURRENCY: usize = 8;
+
+#[derive(Clone, serde::Serialize)]
+struct ScanBatch {
+    root: String,
+    entries: Vec<ScanEntry>,
+}
+
+fn build_globset(patterns: &[String]) -> Result<Option<GlobSet>, String> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| e.to_string())?);
+    }
+    builder.build().map(Some).map_err(|e| e.to_string())
+}
+
+pub fn is_video_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| VIDEO_EXTENSIONS.iter().any(|v| v.eq_ignore_ascii_case(ext)))
+}
+
+/// True for files produced by `process_video` under the default naming:
+/// outputs ending in `_q{quality}_{codec}` for an encoder in
+/// `tools::ENCODERS` (other names would mistake files like
+/// `report_q3_2023.mp4` for outputs), leftover
+/// intermediate files and segments. Outputs named by a custom template are
+/// only recognised through the processed files database.
+pub fn is_own_output(path: &Path) -> bool {
+    let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+        return false;
+    };
+
+    if stem.ends_with("_workinprogress") || stem.ends_with("_tagged_temp") {
+        return true;
+    }
+
+    // Segments of a resumable encode
+    let in_segment_dir = path.parent()
+        .and_then(|p| p.file_name())
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.ends_with(SEGMENT_DIR_SUFFIX));
+    if in_segment_dir && stem.starts_with("segment_") {
+        return true;
+    }
+
+    tools::ENCODERS.iter().any(|(codec, _)| {
+        stem.strip_suffix(codec)
+            .and_then(|rest| rest.strip_suffix('_'))
+            .and_then(|rest| rest.rsplit_once("_q"))
+            .is_some_and(|(_, quality)| !quality.is_empty() && quality.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+/// Walks `root` on a blocking thread, sending every candidate video file.
+/// Files we produced ourselves are counted instead of sent.
+/// `include` patterns must match the path relative to `root` (when given);
+/// `exclude` patterns also prune whole directories.
+fn walk_videos(app: AppHandle, root: PathBuf, include: Option<GlobSet>, exclude: Option<GlobSet>, tx: mpsc::Sender<PathBuf>) -> usize {
+    let mut skipped_outputs = 0;
+    let excluded = |path: &Path| {
+        exclude.as_ref().is_some_and(|set| set.is_match(path.strip_prefix(&root).unwrap_or(path)))
+    };
+
+    let walker = walkdir::WalkDir::new(&root)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !excluded(entry.path()));
+
+    for entry in walker.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !entry.file_type().is_file() || !is_video_file(path) {
+            continue;
+        }
+        if let Some(include) = &include {
+            if !include.is_match(path.strip_prefix(&root).unwrap_or(path)) {
+                continue;
+            }
+        }
+        if is_own_output(path) || processed::is_recorded_output(&app, path) {
+            skipped_outputs += 1;
+            continue;
+        }
+        if tx.blocking_send(path.to_path_buf()).is_err() {
+            break; // Receiver gone, scan was abandoned
+        }
+    }
+
+    skipped_outputs
+}
+
+/// Recursively collects video files under `root`, checks each with
+/// `check_file_status`, and streams them to the frontend in `scan-files`
+/// batches so large shares don't have to be walked before anything shows up.
+#[tauri::command]
+pub async fn scan_directory(window: Window, root: String, include: Vec<String>, exclude: Vec<String>) -> Result<ScanSummary, String> {
+    let root_path = PathBuf::from(&root);
+    if !root_path.is_dir() {
+        return Err(format!("Not a directory: {}", root));
+    }
+
+    let include_set = build_globset(&include)?;
+    let exclude_set = build_globset(&exclude)?;
+
+    let (tx, mut rx) = mpsc::channel::<PathBuf>(SCAN_BATCH_SIZE * 4);
+    let walker_app = window.app_handle().clone();
+    let walker = tauri::async_runtime::spawn_blocking(move || walk_videos(walker_app, root_path, include_set, exclude_set, tx));
+
+    let mut checks = JoinSet::new();
+    let mut batch = Vec::with_capacity(SCAN_BATCH_SIZE);
+    let mut summary = ScanSummary { root: root.clone(), found: 0, pending: 0, skipped: 0, errors: 0, skipped_outputs: 0 };
+    let mut walking = true;
+
+    while walking || !checks.is_empty() {
+        // Keep the probe pool full while the walker is still producing paths
+        if walking && checks.len() < SCAN_CONCURRENCY {
+            match rx.recv().await {
+                Some(path) => {
+                    let app = window.app_handle().clone();
+                    checks.spawn(async move {
+                        let status = check_file_status(app, path.to_string_lossy().to_string()).await;
+                        (path, status)
+                    });
+                    continue;
+                }
+                None => walking = false,
+            }
+        }
+
+        let Some(joined) = checks.join_next().await else { continue };
+        let (path, status) = joined.map_err(|e| e.to_string())?;
+
+        let (status, error) = match status {
+            Ok(status) => (status, None),
+            Err(e) => ("error".to_string(), Some(e)),
+        };
+        match status.as_str() {
+            "skipped" => summary.skipped += 1,
+            "error" => summary.errors += 1,
+            _ => summary.pending += 1,
+        }
+        summary.found += 1;
+
+        batch.push(ScanEntry {
+            name: path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+            path: path.to_string_lossy().to_string(),
+            status,
+            error,
+        });
+
+        if batch.len() >= SCAN_BATCH_SIZE {
+            window.emit("scan-files", ScanBatch { root: root.clone(), entries: std::mem::take(&mut batch) }).map_err(|e| e.to_string())?;
+        }
+    }
+
+    if !batch.is_empty() {
+        window.emit("scan-files", ScanBatch { root: root.clone(), entries: batch }).map_err(|e| e.to_string())?;
+    }
+
+    summary.skipped_outputs = walker.await.map_err(|e| e.to_string())?;
+    window.emit("scan-complete", summary.clone()).map_err(|e| e.to_string())?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_default_output_names() {
+        assert!(is_own_output(Path::new("/videos/holiday_stab_denoise__q23_libx264.mp4")));
+        assert!(is_own_output(Path::new("/videos/holiday___q30_libaom-av1.mkv")));
+        assert!(is_own_output(Path::new("/videos/holiday_q23_hevc_nvenc.mp4")));
+    }
+
+    #[test]
+    fn ignores_names_that_only_look_like_outputs() {
+        assert!(!is_own_output(Path::new("/videos/report_q3_2023.mp4")));
+        assert!(!is_own_output(Path::new("/videos/holiday_q23_libx264_final.mp4")));
+        assert!(!is_own_output(Path::new("/videos/holiday_q_libx264.mp4")));
+        assert!(!is_own_output(Path::new("/videos/holiday_qa1_h264.mp4")));
+    }
+}
@@ -0,0 +1,294 @@
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use chrono::{Local, NaiveTime};
+use sysinfo::System;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Notify;
+
+use crate::settings;
+use crate::job::Job;
+use crate::models::{Schedule, ScheduleCloseAction, ScheduleStatus, TimeWindow};
+use crate::{AppState, LogPayload};
+
+
+/// How often the schedule is re-evaluated.
+const SCHEDULE_POLL: Duration = Duration::from_secs(30);
+
+/// Idle threshold when `idle_cpu_percent` isn't set.
+const DEFAULT_IDLE_CPU_PERCENT: f32 = 20.0;
+
+/// The saved schedule plus what it has done to the running job.
+pub struct ScheduleState {
+    schedule: RwLock<Schedule>,
+    /// Woken when the schedule is changed, so waiting queues re-check it at once.
+    changed: Notify,
+    /// Last status emitted, so `schedule-status` is only sent on changes.
+    status: Mutex<Option<ScheduleStatus>>,
+    /// Job the schedule paused (or, with `finish_file`, let run) while
+    /// closed. It is only acted on once per closure, so a job the user
+    /// resumes by hand isn't paused again.
+    handled: Mutex<Option<Arc<Job>>>,
+}
+
+fn current(app: &AppHandle) -> Schedule {
+    app.state::<ScheduleState>().schedule.read().map(|s| s.clone()).unwrap_or_default()
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").map_err(|_| format!("Invalid time \"{}\", expected HH:MM", value))
+}
+
+fn window_contains(window: &TimeWindow, now: NaiveTime) -> bool {
+    let (Ok(start), Ok(end)) = (parse_time(&window.start), parse_time(&window.end)) else {
+        return false;
+    };
+    if start < end {
+        start <= now && now < end
+    } else {
+        // Wraps past midnight; equal times mean all day
+        now >= start || now < end
+    }
+}
+
+/// Whether the machine runs on AC power, or `None` when it has no battery
+/// or the power source can't be determined.
+#[cfg(target_os = "linux")]
+fn on_ac_power() -> Option<bool> {
+    let mut has_battery = false;
+    for entry in std::fs::read_dir("/sys/class/power_supply").ok()?.filter_map(|e| e.ok()) {
+        let read = |name: &str| std::fs::read_to_string(entry.path().join(name)).map(|v| v.trim().to_string()).unwrap_or_default();
+        match read("type").as_str() {
+            "Mains" | "USB" if read("online") == "1" => return Some(true),
+            "Battery" => has_battery = true,
+            _ => {}
+        }
+    }
+    has_battery.then_some(false)
+}
+
+#[cfg(target_os = "macos")]
+fn on_ac_power() -> Option<bool> {
+    let output = std::process::Command::new("pmset").args(["-g", "batt"]).output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let first_line = text.lines().next()?;
+    Some(first_line.contains("AC Power"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn on_ac_power() -> Option<bool> {
+    None
+}
+
+/// System-wide CPU usage in percent, sampled over a short interval.
+async fn cpu_usage() -> f32 {
+    let mut system = System::new();
+    system.refresh_cpu_usage();
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL.max(Duration::from_millis(500))).await;
+    system.refresh_cpu_usage();
+    system.global_cpu_usage()
+}
+
+/// Why encodes may not run right now under `schedule`, or `None` if they
+/// may. Idleness is only checked with `check_idle`, since a running encode
+/// keeps the CPU busy itself.
+async fn closed_reason(schedule: &Schedule, check_idle: bool) -> Option<String> {
+    if !schedule.enabled {
+        return None;
+    }
+    if !schedule.windows.is_empty() {
+        let now = Local::now().time();
+        if !schedule.windows.iter().any(|w| window_contains(w, now)) {
+            let hours: Vec<String> = schedule.windows.iter().map(|w| format!("{}-{}", w.start, w.end)).collect();
+            return Some(format!("outside the scheduled hours ({})", hours.join(", ")));
+        }
+    }
+    if schedule.require_ac_power && on_ac_power() == Some(false) {
+        return Some("running on battery".to_string());
+    }
+    if schedule.require_idle && check_idle {
+        let threshold = schedule.idle_cpu_percent.unwrap_or(DEFAULT_IDLE_CPU_PERCENT);
+        let usage = cpu_usage().await;
+        if usage >= threshold {
+            return Some(format!("the machine is busy (CPU at {:.0}%)", usage));
+        }
+    }
+    None
+}
+
+/// Emits `schedule-status` if it differs from the last one sent.
+fn report(app: &AppHandle, reason: &Option<String>) {
+    let status = ScheduleStatus { open: reason.is_none(), reason: reason.clone() };
+    let state = app.state::<ScheduleState>();
+    let Ok(mut last) = state.status.lock() else { return };
+    if last.as_ref() != Some(&status) {
+        let _ = app.emit("schedule-status", status.clone());
+        *last = Some(status);
+    }
+}
+
+/// Waits until the schedule allows starting an encode. Queues call this
+/// before taking their next file.
+pub async fn wait_until_open(app: &AppHandle) {
+    let state = app.state::<ScheduleState>();
+    loop {
+        let changed = state.changed.notified();
+        let reason = closed_reason(&current(app), true).await;
+        report(app, &reason);
+        if reason.is_none() {
+            return;
+        }
+        tokio::select! {
+            _ = changed => {}
+            _ = tokio::time::sleep(SCHEDULE_POLL) => {}
+        }
+    }
+}
+
+fn log_job(app: &AppHandle, job: &Job, message: String) {
+    let _ = app.emit("processing-log", LogPayload { path: job.path().to_string(), message });
+}
+
+/// Pauses the running job when the schedule closes (unless it may finish
+/// its file) and resumes it when the schedule opens again.
+async fn apply_to_running_job(app: &AppHandle) {
+    let schedule = current(app);
+    let running = app.state::<AppState>().current_job.lock().ok().and_then(|job| job.clone());
+    let reason = closed_reason(&schedule, running.is_none()).await;
+    report(app, &reason);
+
+    let state = app.state::<ScheduleState>();
+    let Ok(mut handled) = state.handled.lock() else { return };
+    match (reason, running) {
+        (Some(reason), Some(job)) => {
+            if handled.as_ref().is_some_and(|h| Arc::ptr_eq(h, &job)) {
+                return;
+            }
+            if schedule.on_close == ScheduleCloseAction::Pause {
+                match job.pause() {
+                    Ok(()) => log_job(app, &job, format!("Schedule closed ({}), pausing", reason)),
+                    Err(e) => log_job(app, &job, format!("Schedule closed ({}), but pausing failed: {}", reason, e)),
+                }
+            } else {
+                log_job(app, &job, format!("Schedule closed ({}), finishing this file", reason));
+            }
+            *handled = Some(job);
+        }
+        (None, _) => {
+            if let Some(job) = handled.take() {
+                if job.is_paused() && !job.is_cancelled() {
+                    log_job(app, &job, "Schedule opened, resuming".to_string());
+                    let _ = job.resume();
+                }
+            }
+        }
+        (Some(_), None) => {}
+    }
+}
+
+async fn run_monitor(app: AppHandle) {
+    let state = app.state::<ScheduleState>();
+    loop {
+        let changed = state.changed.notified();
+        apply_to_running_job(&app).await;
+        tokio::select! {
+            _ = changed => {}
+            _ = tokio::time::sleep(SCHEDULE_POLL) => {}
+        }
+    }
+}
+
+/// Loads the saved schedule and starts applying it to running jobs.
+pub fn init(app: &AppHandle) {
+    app.manage(ScheduleState {
+        schedule: RwLock::new(settings::load(app).map(|s| s.schedule).unwrap_or_default()),
+        changed: Notify::new(),
+        status: Mutex::new(None),
+        handled: Mutex::new(None),
+    });
+    tauri::async_runtime::spawn(run_monitor(app.clone()));
+}
+
+pub fn validate(schedule: &Schedule) -> Result<(), String> {
+    for window in &schedule.windows {
+        parse_time(&window.start)?;
+        parse_time(&window.end)?;
+    }
+    if schedule.idle_cpu_percent.is_some_and(|p| !(0.0..=100.0).contains(&p)) {
+        return Err("Idle CPU threshold must be between 0 and 100 percent".to_string());
+    }
+    Ok(())
+}
+
+/// Applies `schedule` straight away, waking queues waiting for it.
+pub fn apply(app: &AppHandle, schedule: Schedule) -> Result<(), String> {
+    let state = app.state::<ScheduleState>();
+    *state.schedule.write().map_err(|_| "Failed to lock schedule")? = schedule;
+    state.changed.notify_waiters();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_schedule(app: AppHandle) -> Schedule {
+    current(&app)
+}
+
+/// Saves `schedule` and applies it straight away.
+#[tauri::command]
+pub fn set_schedule(app: AppHandle, schedule: Schedule) -> Result<(), String> {
+    validate(&schedule)?;
+    settings::update(&app, |settings| settings.schedule = schedule.clone())?;
+    apply(&app, schedule)
+}
+
+/// Whether the schedule currently allows encodes to start, and if not, why.
+#[tauri::command]
+pub async fn get_schedule_status(app: AppHandle) -> Result<ScheduleStatus, String> {
+    let reason = closed_reason(&current(&app), true).await;
+    Ok(ScheduleStatus { open: reason.is_none(), reason })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(start: &str, end: &str) -> TimeWindow {
+        TimeWindow { start: start.to_string(), end: end.to_string() }
+    }
+
+    fn at(time: &str) -> NaiveTime {
+        parse_time(time).unwrap()
+    }
+
+    #[test]
+    fn daytime_window() {
+        let office = window("09:00", "17:30");
+        assert!(window_contains(&office, at("09:00")));
+        assert!(window_contains(&office, at("12:00")));
+        assert!(!window_contains(&office, at("17:30")));
+        assert!(!window_contains(&office, at("08:59")));
+    }
+
+    #[test]
+    fn window_wrapping_past_midnight() {
+        let night = window("22:00", "07:00");
+        assert!(window_contains(&night, at("22:00")));
+        assert!(window_contains(&night, at("23:59")));
+        assert!(window_contains(&night, at("00:00")));
+        assert!(window_contains(&night, at("06:59")));
+        assert!(!window_contains(&night, at("07:00")));
+        assert!(!window_contains(&night, at("12:00")));
+    }
+
+    #[test]
+    fn equal_times_cover_the_whole_day() {
+        let all_day = window("00:00", "00:00");
+        assert!(window_contains(&all_day, at("00:00")));
+        assert!(window_contains(&all_day, at("13:37")));
+    }
+
+    #[test]
+    fn invalid_window_never_contains() {
+        assert!(!window_contains(&window("25:00", "07:00"), at("23:00")));
+        assert!(!window_contains(&window("22:00", "7am"), at("23:00")));
+    }
+}
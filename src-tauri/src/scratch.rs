@@ -0,0 +1,183 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::models::VideoOptions;
+use crate::{AppState, LogPayload};
+
+/// Scratch directories intermediate files were written to, so orphans can be
+/// found again after a crash.
+const SCRATCH_REGISTRY_FILE: &str = "scratch_dirs.json";
+
+static REGISTRY_LOCK: Mutex<()> = Mutex::new(());
+
+fn registry_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
+    Ok(data_dir.join(SCRATCH_REGISTRY_FILE))
+}
+
+fn read_registry(app: &AppHandle) -> Result<BTreeSet<PathBuf>, String> {
+    let path = registry_path(app)?;
+    if !path.exists() {
+        return Ok(BTreeSet::new());
+    }
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+fn write_registry(app: &AppHandle, dirs: &BTreeSet<PathBuf>) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(dirs).map_err(|e| e.to_string())?;
+    std::fs::write(registry_path(app)?, content).map_err(|e| e.to_string())
+}
+
+fn register(app: &AppHandle, dir: &Path) -> Result<(), String> {
+    let _guard = REGISTRY_LOCK.lock().map_err(|_| "Failed to lock scratch registry")?;
+    let mut dirs = read_registry(app)?;
+    if dirs.insert(dir.to_path_buf()) {
+        write_registry(app, &dirs)?;
+    }
+    Ok(())
+}
+
+/// Directory for the intermediate files of an encode writing to
+/// `output_dir`: `options.scratch_dir` when set, otherwise the output
+/// directory itself so moving the finished file into place is a rename.
+/// Only a configured scratch dir is remembered for the orphan cleanup;
+/// files in output dirs rely on their `TempFile` guard.
+pub fn scratch_dir(app: &AppHandle, options: &VideoOptions, output_dir: &Path) -> Result<PathBuf, String> {
+    let Some(dir) = &options.scratch_dir else {
+        return Ok(output_dir.to_path_buf());
+    };
+    let dir = PathBuf::from(dir);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    register(app, &dir)?;
+    Ok(dir)
+}
+
+/// An intermediate file that is deleted when the guard goes out of scope,
+/// whichever way the encode ends. Once moved away there is nothing left to
+/// delete.
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Leaves the file on disk and hands back its path.
+    pub fn keep(mut self) -> PathBuf {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if self.0.exists() {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+}
+
+/// A file written under a temporary name next to its destination and only
+/// moved into place by `commit`, so several files can be prepared before
+/// any of them is replaced. Dropped uncommitted, the temporary file goes.
+pub struct StagedFile {
+    temp: TempFile,
+    path: PathBuf,
+}
+
+impl StagedFile {
+    /// Has `write` fill `{path}.tmp`.
+    pub fn new(path: PathBuf, write: impl FnOnce(&Path) -> Result<(), String>) -> Result<Self, String> {
+        let mut temp_name = path.file_name().ok_or("Invalid path")?.to_os_string();
+        temp_name.push(".tmp");
+        let temp = TempFile::new(path.with_file_name(temp_name));
+        write(temp.path())?;
+        Ok(Self { temp, path })
+    }
+
+    pub fn commit(self) -> Result<(), String> {
+        std::fs::rename(self.temp.path(), &self.path).map_err(|e| e.to_string())
+    }
+}
+
+/// Scratch directory for preview samples, which aren't moved anywhere
+/// afterwards: `options.scratch_dir` or the system temp dir. Samples left
+/// there are removed by the startup orphan cleanup.
+pub fn preview_dir(app: &AppHandle, options: &VideoOptions) -> Result<PathBuf, String> {
+    let Some(dir) = &options.scratch_dir else {
+        return Ok(std::env::temp_dir());
+    };
+    let dir = PathBuf::from(dir);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    register(app, &dir)?;
+    Ok(dir)
+}
+
+/// True for intermediate files we name `{stem}_{uuid}_workinprogress.mp4`,
+/// `{stem}_{uuid}.trf` or `{stem}_{uuid}_preview.{mp4,trf}`. The UUID keeps
+/// a user's own files from being mistaken for ours.
+fn is_orphan(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else { return false };
+    let Some(stem) = name.strip_suffix("_workinprogress.mp4")
+        .or_else(|| name.strip_suffix("_preview.mp4"))
+        .or_else(|| name.strip_suffix("_preview.trf"))
+        .or_else(|| name.strip_suffix(".trf"))
+    else {
+        return false;
+    };
+    stem.rsplit('_').next().is_some_and(|id| uuid::Uuid::parse_str(id).is_ok())
+}
+
+/// Deletes intermediate files left behind by runs that crashed or were
+/// killed, from the system temp dir and every configured scratch dir used
+/// before; dirs that can't be read now are skipped, not forgotten.
+/// Only call this while no encode is running. Returns the files that
+/// couldn't be deleted, with the reason.
+fn cleanup_orphans(app: &AppHandle) -> Result<Vec<(PathBuf, String)>, String> {
+    let _guard = REGISTRY_LOCK.lock().map_err(|_| "Failed to lock scratch registry")?;
+    let dirs = read_registry(app)?;
+
+    let mut failures = Vec::new();
+    for dir in dirs.iter().cloned().chain(std::iter::once(std::env::temp_dir())) {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.is_file() && is_orphan(&path) {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    failures.push((path, e.to_string()));
+                }
+            }
+        }
+    }
+    Ok(failures)
+}
+
+/// Cleans up orphaned intermediate files in the background at startup,
+/// holding the encode lock so an early encode's files are left alone.
+/// Files that couldn't be deleted are reported through `processing-log`.
+pub fn init(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let _encode_guard = state.encode_lock.lock().await;
+        let cleanup_app = app.clone();
+        let failures = match tauri::async_runtime::spawn_blocking(move || cleanup_orphans(&cleanup_app)).await {
+            Ok(Ok(failures)) => failures,
+            Ok(Err(e)) => vec![(PathBuf::new(), e)],
+            Err(e) => vec![(PathBuf::new(), e.to_string())],
+        };
+        for (path, error) in failures {
+            let _ = app.emit("processing-log", LogPayload {
+                path: path.to_string_lossy().to_string(),
+                message: format!("Failed to clean up intermediate files: {}", error),
+            });
+        }
+    });
+}
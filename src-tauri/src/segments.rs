@@ -0,0 +1,348 @@
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::task::JoinSet;
+
+use crate::job::{self, Job};
+use crate::models::VideoOptions;
+use crate::tools;
+use crate::LogPayload;
+
+/// Target segment length when `segment_secs` isn't set.
+pub const DEFAULT_SEGMENT_SECS: f64 = 120.0;
+
+/// Suffix of the per-job directories holding segments and their manifest.
+pub const SEGMENT_DIR_SUFFIX: &str = "_segments";
+
+const MANIFEST_FILE: &str = "manifest.json";
+const CONCAT_LIST_FILE: &str = "concat.txt";
+
+/// Output arguments that cut the input themselves; segments can't be
+/// combined with them.
+const TIMING_ARGS: &[&str] = &["-ss", "-t", "-to", "-frames:v", "-vframes"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Segment {
+    index: usize,
+    start_secs: f64,
+    /// `None` for the last segment, which runs to the end of the input.
+    end_secs: Option<f64>,
+    done: bool,
+}
+
+impl Segment {
+    fn file_name(&self) -> String {
+        format!("segment_{:05}.mp4", self.index)
+    }
+}
+
+/// Progress of a segmented encode, kept next to the segments so a later run
+/// with the same input and settings only redoes what's missing.
+#[derive(Serialize, Deserialize, Debug)]
+struct Manifest {
+    input: String,
+    source_size: u64,
+    source_modified: u64,
+    encoding: Vec<String>,
+    segments: Vec<Segment>,
+}
+
+impl Manifest {
+    fn load(dir: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(dir.join(MANIFEST_FILE)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save(&self, dir: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let temp_path = dir.join(format!("{}.tmp", MANIFEST_FILE));
+        std::fs::write(&temp_path, content).map_err(|e| e.to_string())?;
+        std::fs::rename(&temp_path, dir.join(MANIFEST_FILE)).map_err(|e| e.to_string())
+    }
+
+    /// True if this manifest was written for the same, unchanged input and
+    /// the same encoding settings.
+    fn matches(&self, other: &Manifest) -> bool {
+        self.input == other.input
+            && self.source_size == other.source_size
+            && self.source_modified == other.source_modified
+            && self.encoding == other.encoding
+    }
+}
+
+/// Why `options` can't be encoded in segments, if they can't.
+pub fn unsupported_reason(options: &VideoOptions, modifier_args: &[String]) -> Option<&'static str> {
+    if options.stabilize {
+        return Some("stabilization analyses the whole file in one pass");
+    }
+    if modifier_args.iter().any(|arg| TIMING_ARGS.contains(&arg.as_str())) {
+        return Some("a modifier trims the input");
+    }
+    None
+}
+
+/// Timestamps (relative to the start of the file, as `-ss` expects) of the
+/// video keyframes of `input`, read from packet flags without decoding.
+/// ffprobe's output is streamed, since long files have millions of packets
+/// and only the keyframes are kept.
+async fn keyframe_times(input: &Path) -> Result<Vec<f64>, String> {
+    let mut child = tools::ffprobe_command()
+        .args([
+            "-v", "error",
+            "-select_streams", "v:0",
+            "-show_entries", "packet=pts_time,flags:format=start_time",
+            "-of", "csv=p=0",
+        ])
+        .arg(input)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(tools::ffprobe_spawn_error)?;
+    let stdout = child.stdout.take().ok_or("Failed to read ffprobe output")?;
+
+    // Packets come as `pts_time,flags`, followed by the format's `start_time`
+    let mut times = Vec::new();
+    let mut start_time = 0.0;
+    let mut lines = BufReader::new(stdout).lines();
+    while let Some(line) = lines.next_line().await.map_err(|e| e.to_string())? {
+        match line.trim().split_once(',') {
+            Some((pts_time, flags)) if flags.starts_with('K') => {
+                if let Ok(time) = pts_time.parse::<f64>() {
+                    times.push(time);
+                }
+            }
+            Some(_) => {}
+            None => start_time = line.trim().parse().unwrap_or(0.0),
+        }
+    }
+
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err("Failed to run ffprobe".to_string());
+    }
+
+    let mut times: Vec<f64> = times.into_iter().map(|t| (t - start_time).max(0.0)).collect();
+    times.sort_by(|a, b| a.total_cmp(b));
+    Ok(times)
+}
+
+/// Cuts at the first keyframe at least `segment_secs` after the previous cut.
+fn plan_segments(keyframes: &[f64], segment_secs: f64) -> Vec<Segment> {
+    let mut starts = vec![0.0];
+    for &time in keyframes {
+        if time >= starts[starts.len() - 1] + segment_secs {
+            starts.push(time);
+        }
+    }
+
+    starts.iter().enumerate()
+        .map(|(index, &start_secs)| Segment {
+            index,
+            start_secs,
+            end_secs: starts.get(index + 1).copied(),
+            done: false,
+        })
+        .collect()
+}
+
+/// Directory for the segments of `input` encoded with `encoding`. The name
+/// is derived from both, so a rerun with the same settings finds it again.
+fn segment_dir(scratch_dir: &Path, input: &Path, encoding: &[String]) -> Result<PathBuf, String> {
+    let stem = input.file_stem().ok_or("Invalid filename")?.to_string_lossy();
+    let mut hasher = Sha256::new();
+    hasher.update(input.to_string_lossy().as_bytes());
+    for arg in encoding {
+        hasher.update([0]);
+        hasher.update(arg.as_bytes());
+    }
+    let key: String = hasher.finalize().iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    Ok(scratch_dir.join(format!("{}_{}{}", stem, key, SEGMENT_DIR_SUFFIX)))
+}
+
+/// Encodes one segment, video only, into `dir`. The file only gets its
+/// final name once complete, so a crash never leaves a truncated segment
+/// that looks finished.
+async fn encode_segment(app: &AppHandle, job: &Job, input: &Path, options: &VideoOptions, encoding: &[String], segment: &Segment, dir: &Path) -> Result<(), String> {
+    let final_path = dir.join(segment.file_name());
+    let partial_path = dir.join(format!("{}.partial.mp4", segment.file_name().trim_end_matches(".mp4")));
+    if partial_path.exists() {
+        std::fs::remove_file(&partial_path).map_err(|e| e.to_string())?;
+    }
+
+    let mut args = vec!["-n".to_string()];
+    if options.hwaccel != "none" {
+        args.push("-hwaccel".to_string());
+        args.push(options.hwaccel.clone());
+    }
+    args.push("-ss".to_string());
+    args.push(format!("{:.6}", segment.start_secs));
+    args.push("-i".to_string());
+    args.push(input.to_string_lossy().to_string());
+    if let Some(end_secs) = segment.end_secs {
+        args.push("-t".to_string());
+        args.push(format!("{:.6}", end_secs - segment.start_secs));
+    }
+    args.extend(["-map".to_string(), "0:v:0".to_string(), "-an".to_string(), "-sn".to_string()]);
+    args.extend(encoding.iter().cloned());
+    args.push(partial_path.to_string_lossy().to_string());
+
+    let log_path = input.to_string_lossy().to_string();
+    let status = job::run_ffmpeg(app, job, &args, &log_path).await;
+    match status {
+        Ok(status) if status.success() => std::fs::rename(&partial_path, &final_path).map_err(|e| e.to_string()),
+        Ok(status) => {
+            let _ = std::fs::remove_file(&partial_path);
+            Err(format!("Encoding segment {} failed. Status: {}", segment.index + 1, status))
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial_path);
+            Err(e)
+        }
+    }
+}
+
+/// Encodes `input` in keyframe-aligned segments next to `output`, up to
+/// `segment_workers` at a time, recording each finished segment in a manifest, then joins them losslessly with the
+/// concat demuxer into `output`, taking audio straight from the source and
+/// adding `tag_args`. A cancelled or crashed run leaves the segments in
+/// place, and the next run with the same settings only encodes the rest.
+pub async fn encode_segmented(app: &AppHandle, job: &Arc<Job>, input: &Path, options: &VideoOptions, encoding: &[String], output: &Path, tag_args: &[String]) -> Result<ExitStatus, String> {
+    let log_path = input.to_string_lossy().to_string();
+    let log = |message: String| {
+        let _ = app.emit("processing-log", LogPayload { path: log_path.clone(), message });
+    };
+
+    let scratch_dir = output.parent().ok_or("Invalid output path")?;
+    let dir = segment_dir(scratch_dir, input, encoding)?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let metadata = std::fs::metadata(input).map_err(|e| e.to_string())?;
+    let mut manifest = Manifest {
+        input: log_path.clone(),
+        source_size: metadata.len(),
+        source_modified: metadata.modified().ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        encoding: encoding.to_vec(),
+        segments: Vec::new(),
+    };
+
+    match Manifest::load(&dir) {
+        Some(previous) if previous.matches(&manifest) => manifest.segments = previous.segments,
+        _ => {
+            let segment_secs = options.segment_secs.filter(|s| *s > 0.0).unwrap_or(DEFAULT_SEGMENT_SECS);
+            manifest.segments = plan_segments(&keyframe_times(input).await?, segment_secs);
+        }
+    }
+    // A segment only counts as done while its file is still there
+    for segment in &mut manifest.segments {
+        segment.done = segment.done && dir.join(segment.file_name()).exists();
+    }
+    manifest.save(&dir)?;
+
+    let total = manifest.segments.len();
+    let done = manifest.segments.iter().filter(|s| s.done).count();
+    let workers = options.segment_workers.unwrap_or(1).max(1);
+    if done > 0 {
+        log(format!("Resuming segmented encode: {} of {} segments already done", done, total));
+    } else {
+        log(format!("Encoding in {} segments", total));
+    }
+    if workers > 1 {
+        log(format!("Encoding up to {} segments in parallel", workers));
+    }
+
+    // Keep `workers` segment encodes running; the manifest is only touched here
+    let mut remaining = manifest.segments.iter().filter(|s| !s.done).cloned().collect::<Vec<_>>().into_iter();
+    let mut running = JoinSet::new();
+    loop {
+        while running.len() < workers {
+            let Some(segment) = remaining.next() else { break };
+            log(format!("Encoding segment {}/{}", segment.index + 1, total));
+            let (app, job, input, options, encoding, dir) = (app.clone(), job.clone(), input.to_path_buf(), options.clone(), encoding.to_vec(), dir.clone());
+            running.spawn(async move {
+                encode_segment(&app, &job, &input, &options, &encoding, &segment, &dir).await.map(|_| segment.index)
+            });
+        }
+
+        let Some(joined) = running.join_next().await else { break };
+        // Dropping the set on error stops the other workers' ffmpeg processes
+        let index = joined.map_err(|e| e.to_string())??;
+        if let Some(segment) = manifest.segments.iter_mut().find(|s| s.index == index) {
+            segment.done = true;
+        }
+        manifest.save(&dir)?;
+    }
+
+    // Join the segments, adding the source's audio and metadata once
+    let list: String = manifest.segments.iter()
+        .map(|s| format!("file '{}'\n", s.file_name()))
+        .collect();
+    let list_path = dir.join(CONCAT_LIST_FILE);
+    std::fs::write(&list_path, list).map_err(|e| e.to_string())?;
+
+    let mut args = vec![
+        "-n".to_string(),
+        "-f".to_string(), "concat".to_string(),
+        "-safe".to_string(), "0".to_string(),
+        "-i".to_string(), list_path.to_string_lossy().to_string(),
+        "-i".to_string(), log_path.clone(),
+        "-map".to_string(), "0:v".to_string(),
+        "-map".to_string(), "1:a?".to_string(),
+        "-c".to_string(), "copy".to_string(),
+        "-map_metadata".to_string(), "1".to_string(),
+        "-movflags".to_string(), "+faststart".to_string(),
+    ];
+    args.extend(tag_args.iter().cloned());
+    args.push(output.to_string_lossy().to_string());
+
+    log(format!("Joining segments: ffmpeg {}", args.join(" ")));
+    let status = job::run_ffmpeg(app, job, &args, &log_path).await?;
+    if status.success() {
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(segments: &[Segment]) -> Vec<(f64, Option<f64>)> {
+        segments.iter().map(|s| (s.start_secs, s.end_secs)).collect()
+    }
+
+    #[test]
+    fn short_input_is_one_segment() {
+        let segments = plan_segments(&[0.0, 2.0, 4.0, 6.0], 120.0);
+        assert_eq!(bounds(&segments), vec![(0.0, None)]);
+    }
+
+    #[test]
+    fn cuts_at_first_keyframe_after_segment_length() {
+        let keyframes = [0.0, 50.0, 100.0, 125.0, 200.0, 250.0, 260.0];
+        let segments = plan_segments(&keyframes, 120.0);
+        assert_eq!(bounds(&segments), vec![(0.0, Some(125.0)), (125.0, Some(250.0)), (250.0, None)]);
+        assert_eq!(segments.iter().map(|s| s.index).collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn last_segment_may_be_shorter() {
+        let segments = plan_segments(&[0.0, 10.0, 20.0, 25.0], 10.0);
+        assert_eq!(bounds(&segments), vec![(0.0, Some(10.0)), (10.0, Some(20.0)), (20.0, None)]);
+    }
+
+    #[test]
+    fn no_keyframes_is_one_segment() {
+        let segments = plan_segments(&[], 120.0);
+        assert_eq!(bounds(&segments), vec![(0.0, None)]);
+        assert!(!segments[0].done);
+    }
+}
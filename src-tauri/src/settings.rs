@@ -0,0 +1,173 @@
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+use crate::get_config_path;
+use crate::models::{ResourceLimits, Schedule, Settings, ToolPaths, VideoOptions};
+use crate::scratch::StagedFile;
+use crate::{limits, schedule, tools, warnings, watch};
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// Version of the `settings.json` layout written by this build. Files from
+/// a newer version are never overwritten.
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// Serializes read-modify-write cycles on the settings file.
+static SETTINGS_LOCK: Mutex<()> = Mutex::new(());
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            defaults: VideoOptions::default(),
+            ffmpeg: ToolPaths::default(),
+            presets: Vec::new(),
+            limits: ResourceLimits::default(),
+            schedule: Schedule::default(),
+            watch_folders: Vec::new(),
+        }
+    }
+}
+
+fn newer_version(version: u32) -> String {
+    format!("{} has version {}, but this build only understands up to {}", SETTINGS_FILE, version, SETTINGS_VERSION)
+}
+
+fn check_version(value: &serde_json::Value) -> Result<(), String> {
+    let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if version > SETTINGS_VERSION {
+        return Err(newer_version(version));
+    }
+    Ok(())
+}
+
+/// Settings from `value`, e.g. taken from a config bundle. Settings from a
+/// newer version are refused.
+pub fn from_value(value: serde_json::Value) -> Result<Settings, String> {
+    check_version(&value)?;
+    serde_json::from_value(value).map_err(|e| format!("Invalid settings: {}", e))
+}
+
+/// Reads the settings, or the defaults when there is no file yet.
+fn read(app: &AppHandle) -> Result<Settings, String> {
+    let path = get_config_path(app, SETTINGS_FILE)?;
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let value: serde_json::Value = serde_json::from_str(&content).map_err(|e| format!("Invalid {}: {}", SETTINGS_FILE, e))?;
+    check_version(&value)?;
+    serde_json::from_value(value).map_err(|e| format!("Invalid {}: {}", SETTINGS_FILE, e))
+}
+
+fn stage(app: &AppHandle, settings: &Settings) -> Result<StagedFile, String> {
+    let content = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    StagedFile::new(get_config_path(app, SETTINGS_FILE)?, |temp_path| std::fs::write(temp_path, content).map_err(|e| e.to_string()))
+}
+
+fn write(app: &AppHandle, settings: &Settings) -> Result<(), String> {
+    stage(app, settings)?.commit()
+}
+
+/// Current settings.
+pub fn load(app: &AppHandle) -> Result<Settings, String> {
+    read(app)
+}
+
+/// Applies `change` to the stored settings and saves them.
+pub fn update(app: &AppHandle, change: impl FnOnce(&mut Settings)) -> Result<Settings, String> {
+    let _guard = SETTINGS_LOCK.lock().map_err(|_| "Failed to lock settings")?;
+    let mut settings = read(app).map_err(|e| format!("Not saving settings: {}", e))?;
+    change(&mut settings);
+    write(app, &settings)?;
+    Ok(settings)
+}
+
+/// Reports settings that can't be used (unreadable, or from a newer build)
+/// at startup. Until they are fixed, every module runs on its defaults and
+/// nothing is saved over them.
+pub fn init(app: &AppHandle) {
+    if let Err(e) = read(app) {
+        warnings::report(app, SETTINGS_FILE, format!("{}. Using default settings; changes won't be saved.", e));
+    }
+}
+
+/// Saves `settings`. Changed ffmpeg paths, limits, schedule and watch
+/// folders take effect straight away.
+pub async fn save(app: &AppHandle, settings: Settings) -> Result<Settings, String> {
+    update_with(app, |_| Ok((settings, Vec::new()))).await
+}
+
+/// Has `change` build new settings from the stored ones, along with other
+/// files to replace, and saves them like `save`, all while holding the
+/// settings lock. Nothing is replaced until every file, the settings
+/// included, has been written in full.
+pub async fn update_with(app: &AppHandle, change: impl FnOnce(Settings) -> Result<(Settings, Vec<StagedFile>), String>) -> Result<Settings, String> {
+    let (previous, saved) = {
+        let _guard = SETTINGS_LOCK.lock().map_err(|_| "Failed to lock settings")?;
+        let previous = read(app).map_err(|e| format!("Not saving settings: {}", e))?;
+        let (settings, files) = change(previous.clone())?;
+        validate(&settings)?;
+        let saved = Settings { version: SETTINGS_VERSION, ..settings };
+        let staged = stage(app, &saved)?;
+        for file in files {
+            file.commit()?;
+        }
+        staged.commit()?;
+        (previous, saved)
+    };
+
+    if previous.limits != saved.limits {
+        limits::apply(saved.limits.clone())?;
+    }
+    if previous.schedule != saved.schedule {
+        schedule::apply(app, saved.schedule.clone())?;
+    }
+    if previous.watch_folders != saved.watch_folders {
+        watch::apply(app, saved.watch_folders.clone())?;
+    }
+    if previous.ffmpeg != saved.ffmpeg {
+        tools::apply_paths(saved.ffmpeg.clone())?;
+        tools::check(app).await;
+    }
+    Ok(saved)
+}
+
+fn validate_defaults(defaults: &VideoOptions) -> Result<(), String> {
+    if defaults.quality > 63 {
+        return Err("Quality must be between 0 and 63".to_string());
+    }
+    if defaults.segment_workers == Some(0) {
+        return Err("Segment workers must be at least 1".to_string());
+    }
+    Ok(())
+}
+
+/// Checks `settings` before they are saved.
+pub fn validate(settings: &Settings) -> Result<(), String> {
+    validate_defaults(&settings.defaults)?;
+    let mut names = std::collections::HashSet::new();
+    if let Some(preset) = settings.presets.iter().find(|p| !names.insert(p.name.as_str())) {
+        return Err(format!("Duplicate preset name: {}", preset.name));
+    }
+    limits::validate(&settings.limits)?;
+    schedule::validate(&settings.schedule)
+}
+
+#[tauri::command]
+pub fn load_settings(app: AppHandle) -> Result<Settings, String> {
+    load(&app)
+}
+
+#[tauri::command]
+pub async fn save_settings(app: AppHandle, settings: Settings) -> Result<Settings, String> {
+    save(&app, settings).await
+}
+
+/// Saves only the options new sessions start with, leaving the other
+/// sections as they are on disk.
+#[tauri::command]
+pub fn save_default_options(app: AppHandle, defaults: VideoOptions) -> Result<Settings, String> {
+    validate_defaults(&defaults)?;
+    update(&app, |settings| settings.defaults = defaults)
+}
@@ -0,0 +1,148 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use sha2::{Digest, Sha256};
+use tauri::http::{Request, Response, StatusCode};
+use tauri::{Manager, Runtime, UriSchemeContext};
+
+/// URI scheme cached thumbnails are served from.
+pub const THUMBNAIL_SCHEME: &str = "thumb";
+
+/// Default upper bound for the on-disk thumbnail cache.
+pub const THUMBNAIL_CACHE_LIMIT: u64 = 256 * 1024 * 1024;
+
+/// Length of the hex keys issued by `ThumbnailCache::key`.
+const KEY_LEN: usize = 32;
+
+/// Persistent JPEG cache for previews and contact sheets. Entries are keyed
+/// by file identity so they survive restarts, and the least recently used
+/// ones are evicted once the cache grows past `max_bytes`.
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    evict_lock: Mutex<()>,
+}
+
+impl ThumbnailCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes, evict_lock: Mutex::new(()) }
+    }
+
+    /// Identifies a file by path, size and modification time so cached images
+    /// are regenerated whenever the file changes. `variant` distinguishes
+    /// different renderings of the same file.
+    pub fn key(path: &Path, variant: &str) -> Result<String, String> {
+        let metadata = std::fs::metadata(path).map_err(|e| e.to_string())?;
+        let mtime = metadata.modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+
+        // A stable hash, since keys name files that outlive the process
+        let mut hasher = Sha256::new();
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(metadata.len().to_le_bytes());
+        hasher.update(mtime.to_le_bytes());
+        hasher.update(variant.as_bytes());
+        let key: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(key[..KEY_LEN].to_string())
+    }
+
+    /// Maps a key to its file, rejecting anything that isn't a key we issued.
+    fn entry_path(&self, key: &str) -> Option<PathBuf> {
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(self.dir.join(format!("{}.jpg", key)))
+    }
+
+    /// Returns true if `key` is cached, marking it as recently used.
+    pub fn contains(&self, key: &str) -> bool {
+        match self.entry_path(key) {
+            Some(path) if path.exists() => {
+                let _ = filetime::set_file_mtime(&path, filetime::FileTime::now());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn read(&self, key: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.entry_path(key)?).ok()
+    }
+
+    pub fn insert(&self, key: &str, data: &[u8]) -> Result<(), String> {
+        let path = self.entry_path(key).ok_or("Invalid thumbnail key")?;
+        std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+
+        // Write to a temporary name so readers never see a partial image
+        let temp_path = self.dir.join(format!("{}.tmp", uuid::Uuid::new_v4()));
+        std::fs::write(&temp_path, data).map_err(|e| e.to_string())?;
+        if let Err(e) = std::fs::rename(&temp_path, &path) {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(e.to_string());
+        }
+
+        self.evict();
+        Ok(())
+    }
+
+    /// Removes the least recently used entries until the cache fits in `max_bytes`.
+    fn evict(&self) {
+        let Ok(_guard) = self.evict_lock.lock() else { return };
+        let Ok(read_dir) = std::fs::read_dir(&self.dir) else { return };
+
+        let mut entries: Vec<(PathBuf, u64, std::time::SystemTime)> = read_dir
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "jpg"))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((entry.path(), metadata.len(), metadata.modified().ok()?))
+            })
+            .collect();
+
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        if total <= self.max_bytes {
+            return;
+        }
+
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, size, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= size;
+            }
+        }
+    }
+}
+
+/// URL the webview loads a cached thumbnail from.
+pub fn thumbnail_url(key: &str) -> String {
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost/{}", THUMBNAIL_SCHEME, key)
+    } else {
+        format!("{}://localhost/{}", THUMBNAIL_SCHEME, key)
+    }
+}
+
+/// Serves `thumb://localhost/<key>` requests from the thumbnail cache.
+pub fn handle_protocol<R: Runtime>(ctx: UriSchemeContext<'_, R>, request: Request<Vec<u8>>) -> Response<Cow<'static, [u8]>> {
+    let cache = ctx.app_handle().state::<ThumbnailCache>();
+    let key = request.uri().path().trim_start_matches('/');
+
+    match cache.read(key) {
+        Some(data) => Response::builder()
+            .header("Content-Type", "image/jpeg")
+            .header("Cache-Control", "max-age=31536000, immutable")
+            .body(Cow::Owned(data)),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Cow::Borrowed(&[][..])),
+    }
+    .unwrap_or_else(|_| Response::new(Cow::Borrowed(&[][..])))
+}
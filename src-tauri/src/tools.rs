@@ -0,0 +1,282 @@
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tauri::{AppHandle, Emitter};
+use tokio::process::Command;
+
+use crate::models::{FfmpegInfo, ToolInfo, ToolPaths, VideoOptions};
+use crate::settings;
+
+/// Configured binary locations, loaded from the settings at startup.
+static PATHS: RwLock<ToolPaths> = RwLock::new(ToolPaths { ffmpeg: None, ffprobe: None });
+
+/// Result of the last check of the binaries, `None` until one finished.
+static INFO: RwLock<Option<FfmpegInfo>> = RwLock::new(None);
+
+fn configured(path: &Option<String>) -> Option<PathBuf> {
+    path.as_deref().map(str::trim).filter(|p| !p.is_empty()).map(PathBuf::from)
+}
+
+fn paths() -> ToolPaths {
+    PATHS.read().map(|paths| paths.clone()).unwrap_or_default()
+}
+
+/// The ffmpeg binary to run: the configured one, otherwise `ffmpeg` from `PATH`.
+pub fn ffmpeg_program() -> PathBuf {
+    configured(&paths().ffmpeg).unwrap_or_else(|| PathBuf::from("ffmpeg"))
+}
+
+/// The ffprobe binary to run: the configured one, the one next to a
+/// configured ffmpeg (static builds ship both), otherwise `ffprobe` from `PATH`.
+pub fn ffprobe_program() -> PathBuf {
+    let paths = paths();
+    if let Some(ffprobe) = configured(&paths.ffprobe) {
+        return ffprobe;
+    }
+    configured(&paths.ffmpeg)
+        .map(|ffmpeg| ffmpeg.with_file_name(format!("ffprobe{}", std::env::consts::EXE_SUFFIX)))
+        .filter(|sibling| sibling.is_file())
+        .unwrap_or_else(|| PathBuf::from("ffprobe"))
+}
+
+pub fn ffprobe_command() -> Command {
+    Command::new(ffprobe_program())
+}
+
+fn not_found(tool: &str, program: &Path) -> String {
+    format!("{} was not found ({}). Install it or set its location in the settings.", tool, program.display())
+}
+
+fn spawn_error(tool: &str, program: &Path, e: std::io::Error) -> String {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        not_found(tool, program)
+    } else {
+        format!("Failed to run {}: {}", tool, e)
+    }
+}
+
+/// Turns a failure to start ffmpeg into a message saying what's wrong.
+pub fn ffmpeg_spawn_error(e: std::io::Error) -> String {
+    spawn_error("ffmpeg", &ffmpeg_program(), e)
+}
+
+/// Turns a failure to start ffprobe into a message saying what's wrong.
+pub fn ffprobe_spawn_error(e: std::io::Error) -> String {
+    spawn_error("ffprobe", &ffprobe_program(), e)
+}
+
+/// Names from `-filters` output, whose lines look like
+/// ` T.C drawtext          V->V       Draw text on top of video frames...`.
+fn parse_filters(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let _flags = words.next()?;
+            let name = words.next()?;
+            words.next().filter(|io| io.contains("->")).map(|_| name.to_string())
+        })
+        .collect()
+}
+
+/// Runs `program -version` and `program -filters` and reads its version,
+/// build configuration and available filters.
+async fn inspect(tool: &str, program: PathBuf) -> ToolInfo {
+    let mut info = ToolInfo {
+        path: program.to_string_lossy().to_string(),
+        found: false,
+        version: None,
+        configuration: Vec::new(),
+        filters: Vec::new(),
+        error: None,
+    };
+    let output = match Command::new(&program).args(["-hide_banner", "-version"]).output().await {
+        Ok(output) => output,
+        Err(e) => {
+            info.error = Some(spawn_error(tool, &program, e));
+            return info;
+        }
+    };
+    if !output.status.success() {
+        info.error = Some(format!("{} -version failed. Status: {}", program.display(), output.status));
+        return info;
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    info.found = true;
+    info.version = text.lines().next()
+        .and_then(|line| line.split_whitespace().skip_while(|word| *word != "version").nth(1))
+        .map(str::to_string);
+    info.configuration = text.lines()
+        .find_map(|line| line.trim().strip_prefix("configuration:"))
+        .map(|flags| flags.split_whitespace().filter(|f| f.starts_with("--enable-")).map(str::to_string).collect())
+        .unwrap_or_default();
+
+    if let Ok(output) = Command::new(&program).args(["-hide_banner", "-filters"]).output().await {
+        if output.status.success() {
+            info.filters = parse_filters(&String::from_utf8_lossy(&output.stdout));
+        }
+    }
+    info
+}
+
+/// Checks both binaries, remembers the result and emits `ffmpeg-status`.
+pub async fn check(app: &AppHandle) -> FfmpegInfo {
+    let info = FfmpegInfo {
+        ffmpeg: inspect("ffmpeg", ffmpeg_program()).await,
+        ffprobe: inspect("ffprobe", ffprobe_program()).await,
+    };
+    if let Ok(mut last) = INFO.write() {
+        *last = Some(info.clone());
+    }
+    let _ = app.emit("ffmpeg-status", info.clone());
+    info
+}
+
+/// Encoders we know, with the build flag behind each external library.
+/// Native and hardware encoders (auto-detected by ffmpeg's configure) need
+/// no flag; encoders that aren't listed are never refused either. Scans use
+/// the names to recognise outputs named after their encoder.
+pub const ENCODERS: &[(&str, Option<&str>)] = &[
+    ("libx264", Some("--enable-libx264")),
+    ("libx264rgb", Some("--enable-libx264")),
+    ("libx265", Some("--enable-libx265")),
+    ("libvpx", Some("--enable-libvpx")),
+    ("libvpx-vp9", Some("--enable-libvpx")),
+    ("libaom-av1", Some("--enable-libaom")),
+    ("libsvtav1", Some("--enable-libsvtav1")),
+    ("librav1e", Some("--enable-librav1e")),
+    ("libxvid", Some("--enable-libxvid")),
+    ("libtheora", Some("--enable-libtheora")),
+    ("libopenh264", Some("--enable-libopenh264")),
+    ("h264", None), ("hevc", None), ("av1", None), ("vp9", None), ("mpeg4", None),
+    ("h264_nvenc", None), ("hevc_nvenc", None), ("av1_nvenc", None),
+    ("h264_qsv", None), ("hevc_qsv", None), ("av1_qsv", None), ("vp9_qsv", None),
+    ("h264_vaapi", None), ("hevc_vaapi", None), ("av1_vaapi", None), ("vp9_vaapi", None),
+    ("h264_amf", None), ("hevc_amf", None), ("av1_amf", None),
+    ("h264_videotoolbox", None), ("hevc_videotoolbox", None),
+];
+
+/// `--enable-*` flags an encode with `options` can't do without: vid.stab
+/// for stabilization, and the library behind a known external encoder.
+fn required_flags(options: &VideoOptions) -> Vec<String> {
+    let mut flags = Vec::new();
+    if options.stabilize {
+        flags.push("--enable-libvidstab".to_string());
+    }
+    if let Some((_, Some(flag))) = ENCODERS.iter().find(|(encoder, _)| *encoder == options.codec) {
+        flags.push(flag.to_string());
+    }
+    flags
+}
+
+/// Fails up front when the last check found ffmpeg or ffprobe missing, or
+/// an ffmpeg built without something `options` needs. Passes while no check
+/// has finished yet.
+pub fn check_capabilities(options: &VideoOptions) -> Result<(), String> {
+    let Some(info) = INFO.read().ok().and_then(|info| info.clone()) else {
+        return Ok(());
+    };
+    for tool in [&info.ffmpeg, &info.ffprobe] {
+        if !tool.found {
+            return Err(tool.error.clone().unwrap_or_else(|| format!("{} is not usable", tool.path)));
+        }
+    }
+    let missing: Vec<String> = required_flags(options).into_iter()
+        .filter(|flag| !info.ffmpeg.configuration.contains(flag))
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} was built without {}", info.ffmpeg.path, missing.join(", ")))
+    }
+}
+
+/// Whether the checked ffmpeg has the filter `name`. True while no check
+/// has finished or its filter list couldn't be read.
+pub fn has_filter(name: &str) -> bool {
+    let Some(info) = INFO.read().ok().and_then(|info| info.clone()) else {
+        return true;
+    };
+    info.ffmpeg.filters.is_empty() || info.ffmpeg.filters.iter().any(|filter| filter == name)
+}
+
+/// Uses `paths` for every ffmpeg and ffprobe started from now on.
+pub fn apply_paths(paths: ToolPaths) -> Result<(), String> {
+    *PATHS.write().map_err(|_| "Failed to lock ffmpeg paths")? = paths;
+    Ok(())
+}
+
+/// Loads the configured binary paths and checks the binaries in the
+/// background; problems are reported through `ffmpeg-status`.
+pub fn init(app: &AppHandle) {
+    let _ = apply_paths(settings::load(app).map(|s| s.ffmpeg).unwrap_or_default());
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        check(&app).await;
+    });
+}
+
+#[tauri::command]
+pub fn get_ffmpeg_paths() -> ToolPaths {
+    paths()
+}
+
+/// Saves the binary paths and checks the binaries they point to.
+#[tauri::command]
+pub async fn set_ffmpeg_paths(app: AppHandle, paths: ToolPaths) -> Result<FfmpegInfo, String> {
+    for path in [configured(&paths.ffmpeg), configured(&paths.ffprobe)].into_iter().flatten() {
+        if !path.is_file() {
+            return Err(format!("Not a file: {}", path.display()));
+        }
+    }
+
+    settings::update(&app, |settings| settings.ffmpeg = paths.clone())?;
+    apply_paths(paths)?;
+    Ok(check(&app).await)
+}
+
+/// Result of the startup check, running it now if it hasn't finished yet.
+#[tauri::command]
+pub async fn get_ffmpeg_info(app: AppHandle) -> Result<FfmpegInfo, String> {
+    let last = INFO.read().ok().and_then(|info| info.clone());
+    match last {
+        Some(info) => Ok(info),
+        None => Ok(check(&app).await),
+    }
+}
+
+/// Checks the binaries again, e.g. after installing ffmpeg.
+#[tauri::command]
+pub async fn check_ffmpeg(app: AppHandle) -> Result<FfmpegInfo, String> {
+    Ok(check(&app).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags_for(codec: &str) -> Vec<String> {
+        required_flags(&VideoOptions { codec: codec.to_string(), ..VideoOptions::default() })
+    }
+
+    #[test]
+    fn known_encoders_need_their_library() {
+        assert_eq!(flags_for("libx264rgb"), vec!["--enable-libx264"]);
+        assert_eq!(flags_for("libvpx-vp9"), vec!["--enable-libvpx"]);
+        assert_eq!(flags_for("libaom-av1"), vec!["--enable-libaom"]);
+    }
+
+    #[test]
+    fn filter_names_are_read_from_the_listing() {
+        let text = "Filters:\n  T.. = Timeline support\n  .S. = Slice threading\n  ..C = Command support\n  A = Audio input/output\n \
+            ... abench            A->A       Benchmark part of a filtergraph.\n \
+            TSC drawtext          V->V       Draw text on top of video frames using libfreetype library.\n";
+        assert_eq!(parse_filters(text), vec!["abench", "drawtext"]);
+    }
+
+    #[test]
+    fn native_and_unknown_encoders_need_nothing() {
+        assert!(flags_for("mpeg4").is_empty());
+        assert!(flags_for("hevc_nvenc").is_empty());
+        assert!(flags_for("libsomethingnew").is_empty());
+    }
+}
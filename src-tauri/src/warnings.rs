@@ -0,0 +1,23 @@
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+use crate::models::ConfigWarning;
+
+/// Problems found while loading the configuration, kept for a UI that
+/// starts listening only after they were emitted.
+static WARNINGS: Mutex<Vec<ConfigWarning>> = Mutex::new(Vec::new());
+
+/// Records that `source` couldn't be used as stored and emits it as
+/// `config-warning`. The app carries on with defaults for that part.
+pub fn report(app: &AppHandle, source: &str, message: String) {
+    let warning = ConfigWarning { source: source.to_string(), message };
+    if let Ok(mut warnings) = WARNINGS.lock() {
+        warnings.push(warning.clone());
+    }
+    let _ = app.emit("config-warning", warning);
+}
+
+#[tauri::command]
+pub fn get_config_warnings() -> Vec<ConfigWarning> {
+    WARNINGS.lock().map(|warnings| warnings.clone()).unwrap_or_default()
+}
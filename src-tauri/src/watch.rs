@@ -0,0 +1,305 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::mpsc;
+
+use crate::journal;
+use crate::processed;
+use crate::{schedule, settings};
+use crate::models::{FileOperation, WatchEvent, WatchFolder};
+use crate::scan::{is_own_output, is_video_file};
+use crate::{check_file_status, encode_file, move_file, AppState, LogPayload};
+
+/// How long a file's size and mtime must stay unchanged before it is
+/// considered fully copied.
+const SETTLE_DELAY: Duration = Duration::from_secs(5);
+
+/// How often pending files are re-examined.
+const SETTLE_POLL: Duration = Duration::from_secs(1);
+
+struct PendingFile {
+    folder: Arc<WatchFolder>,
+    fingerprint: Option<(u64, SystemTime)>,
+    changed_at: Instant,
+}
+
+struct QueuedFile {
+    path: PathBuf,
+    folder: Arc<WatchFolder>,
+}
+
+/// Filesystem watchers for the configured watch folders plus the files
+/// waiting to settle and the ones already queued for encoding.
+pub struct WatchState {
+    watchers: Mutex<Vec<RecommendedWatcher>>,
+    pending: Arc<Mutex<HashMap<PathBuf, PendingFile>>>,
+    active: Arc<Mutex<HashSet<PathBuf>>>,
+    queue: mpsc::UnboundedSender<QueuedFile>,
+}
+
+fn fingerprint(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// True if `path` should be picked up for `folder`: a video that we didn't
+/// produce ourselves and that isn't inside the folder's output or archive dirs.
+fn is_candidate(folder: &WatchFolder, path: &Path) -> bool {
+    if !is_video_file(path) || is_own_output(path) {
+        return false;
+    }
+    let inside = |dir: &Option<String>| dir.as_ref().is_some_and(|dir| path.starts_with(dir));
+    !inside(&folder.output_dir) && !inside(&folder.archive_dir)
+}
+
+/// Adds (or refreshes) a file that must settle before being queued.
+fn mark_pending(pending: &Mutex<HashMap<PathBuf, PendingFile>>, active: &Mutex<HashSet<PathBuf>>, folder: &Arc<WatchFolder>, path: &Path) {
+    if !is_candidate(folder, path) {
+        return;
+    }
+    if active.lock().map(|active| active.contains(path)).unwrap_or(true) {
+        return;
+    }
+    if let Ok(mut pending) = pending.lock() {
+        let entry = pending.entry(path.to_path_buf()).or_insert_with(|| PendingFile {
+            folder: folder.clone(),
+            fingerprint: None,
+            changed_at: Instant::now(),
+        });
+        entry.changed_at = Instant::now();
+    }
+}
+
+impl WatchState {
+    /// Replaces all watchers with ones for `folders`, picking up videos that
+    /// are already sitting in them. A folder that can't be watched is
+    /// skipped and reported through a `watch-event` with status `error`.
+    fn start(&self, app: &AppHandle, folders: Vec<WatchFolder>) -> Result<(), String> {
+        let mut watchers = self.watchers.lock().map_err(|_| "Failed to lock mutex")?;
+        watchers.clear();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.clear();
+        }
+
+        for folder in folders.into_iter().filter(|f| f.enabled) {
+            let folder = Arc::new(folder);
+            match self.watch(&folder) {
+                Ok(watcher) => watchers.push(watcher),
+                Err(e) => emit_watch_event(app, &folder, Path::new(&folder.path), "error", Some(e)),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn watch(&self, folder: &Arc<WatchFolder>) -> Result<RecommendedWatcher, String> {
+        let root = PathBuf::from(&folder.path);
+        let mode = if folder.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+
+        let handler_folder = folder.clone();
+        let pending = self.pending.clone();
+        let active = self.active.clone();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else { return };
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                for path in &event.paths {
+                    mark_pending(&pending, &active, &handler_folder, path);
+                }
+            }
+        }).map_err(|e| e.to_string())?;
+        watcher.watch(&root, mode).map_err(|e| format!("Failed to watch {}: {}", folder.path, e))?;
+
+        let max_depth = if folder.recursive { usize::MAX } else { 1 };
+        for entry in walkdir::WalkDir::new(&root).max_depth(max_depth).into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_file() {
+                mark_pending(&self.pending, &self.active, folder, entry.path());
+            }
+        }
+        Ok(watcher)
+    }
+
+    /// Moves files whose size and mtime have been stable for `SETTLE_DELAY`
+    /// from the pending set to the encode queue, returning the queued files.
+    fn queue_settled(&self) -> Vec<(PathBuf, Arc<WatchFolder>)> {
+        let Ok(mut pending) = self.pending.lock() else { return Vec::new() };
+        let mut ready = Vec::new();
+
+        pending.retain(|path, file| {
+            let Some(current) = fingerprint(path) else {
+                return false; // Deleted or moved away before settling
+            };
+            if file.fingerprint != Some(current) {
+                file.fingerprint = Some(current);
+                file.changed_at = Instant::now();
+                return true;
+            }
+            if current.0 == 0 || file.changed_at.elapsed() < SETTLE_DELAY {
+                return true;
+            }
+            ready.push((path.clone(), file.folder.clone()));
+            false
+        });
+        drop(pending);
+
+        let mut queued = Vec::new();
+        for (path, folder) in ready {
+            if let Ok(mut active) = self.active.lock() {
+                if !active.insert(path.clone()) {
+                    continue;
+                }
+            }
+            if self.queue.send(QueuedFile { path: path.clone(), folder: folder.clone() }).is_ok() {
+                queued.push((path, folder));
+            }
+        }
+        queued
+    }
+}
+
+fn emit_watch_event(app: &AppHandle, folder: &WatchFolder, path: &Path, status: &str, message: Option<String>) {
+    let _ = app.emit("watch-event", WatchEvent {
+        folder: folder.path.clone(),
+        path: path.to_string_lossy().to_string(),
+        status: status.to_string(),
+        message,
+    });
+}
+
+/// Moves `path` into `dest_root`, keeping its location relative to the
+/// watched folder. Existing files are never overwritten.
+fn relocate(app: &AppHandle, path: &Path, watch_root: &Path, dest_root: &str) -> Result<PathBuf, String> {
+    let relative = path.strip_prefix(watch_root).ok()
+        .and_then(|r| r.parent())
+        .unwrap_or_else(|| Path::new(""));
+    let dest_dir = Path::new(dest_root).join(relative);
+    std::fs::create_dir_all(&dest_dir).map_err(|e| e.to_string())?;
+
+    let dest = dest_dir.join(path.file_name().ok_or("Invalid filename")?);
+    if dest.exists() {
+        return Err(format!("{} already exists", dest.display()));
+    }
+    let mut entry = journal::new_entry(FileOperation::Move, path);
+    move_file(path, &dest)?;
+    entry.new_path = Some(dest.to_string_lossy().to_string());
+    // The file has moved either way, so a journal failure is only logged
+    if let Err(e) = journal::record(app, &entry) {
+        let _ = app.emit("processing-log", LogPayload { path: path.to_string_lossy().to_string(), message: format!("Original archived, but failed to record it in journal: {}", e) });
+    }
+    Ok(dest)
+}
+
+async fn process_queued(app: &AppHandle, job: &QueuedFile) {
+    let folder = &job.folder;
+    let path_str = job.path.to_string_lossy().to_string();
+
+    // Outputs named by a custom template only show up in the database
+    if processed::is_recorded_output(app, &job.path) {
+        emit_watch_event(app, folder, &job.path, "skipped", Some("Output of an earlier encode".to_string()));
+        return;
+    }
+
+    match check_file_status(app.clone(), path_str.clone()).await {
+        Ok(status) if status == "skipped" => {
+            emit_watch_event(app, folder, &job.path, "skipped", None);
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            emit_watch_event(app, folder, &job.path, "error", Some(e));
+            return;
+        }
+    }
+
+    // Outputs go to the folder's output dir, mirroring the watched tree
+    let mut options = folder.options.clone();
+    if folder.output_dir.is_some() {
+        options.output_dir = folder.output_dir.clone();
+        options.mirror_root = Some(folder.path.clone());
+    }
+
+    emit_watch_event(app, folder, &job.path, "processing", None);
+    let state = app.state::<AppState>();
+    let stats = match encode_file(app, &state, path_str, options).await {
+        Ok(stats) => stats,
+        Err(e) => {
+            emit_watch_event(app, folder, &job.path, "error", Some(e));
+            return;
+        }
+    };
+
+    let watch_root = Path::new(&folder.path);
+    let mut notes = vec![format!("Output written to {}", stats.output_path)];
+    if let Some(archive_dir) = &folder.archive_dir {
+        match relocate(app, &job.path, watch_root, archive_dir) {
+            Ok(dest) => notes.push(format!("Original archived to {}", dest.display())),
+            Err(e) => notes.push(format!("Original not archived: {}", e)),
+        }
+    }
+
+    emit_watch_event(app, folder, &job.path, "done", Some(notes.join("; ")));
+}
+
+async fn run_worker(app: AppHandle, mut queue: mpsc::UnboundedReceiver<QueuedFile>) {
+    while let Some(job) = queue.recv().await {
+        schedule::wait_until_open(&app).await;
+        process_queued(&app, &job).await;
+        if let Ok(mut active) = app.state::<WatchState>().active.lock() {
+            active.remove(&job.path);
+        }
+    }
+}
+
+async fn run_settle_loop(app: AppHandle) {
+    let mut ticker = tokio::time::interval(SETTLE_POLL);
+    loop {
+        ticker.tick().await;
+        for (path, folder) in app.state::<WatchState>().queue_settled() {
+            emit_watch_event(&app, &folder, &path, "queued", None);
+        }
+    }
+}
+
+/// Sets up the watch subsystem and starts watching the saved folders.
+pub fn init(app: &AppHandle) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    app.manage(WatchState {
+        watchers: Mutex::new(Vec::new()),
+        pending: Arc::new(Mutex::new(HashMap::new())),
+        active: Arc::new(Mutex::new(HashSet::new())),
+        queue: tx,
+    });
+
+    tauri::async_runtime::spawn(run_worker(app.clone(), rx));
+    tauri::async_runtime::spawn(run_settle_loop(app.clone()));
+
+    let folders = settings::load(app).map(|s| s.watch_folders).unwrap_or_default();
+    if let Err(e) = apply(app, folders) {
+        let _ = app.emit("processing-log", LogPayload { path: String::new(), message: e });
+    }
+}
+
+/// Restarts watching with `folders`.
+pub fn apply(app: &AppHandle, folders: Vec<WatchFolder>) -> Result<(), String> {
+    app.state::<WatchState>().start(app, folders)
+}
+
+#[tauri::command]
+pub fn get_watch_folders(app: AppHandle) -> Result<Vec<WatchFolder>, String> {
+    settings::load(&app).map(|s| s.watch_folders)
+}
+
+/// Saves the watch folder configuration and restarts watching with it.
+#[tauri::command]
+pub fn set_watch_folders(app: AppHandle, folders: Vec<WatchFolder>) -> Result<(), String> {
+    for folder in &folders {
+        if !Path::new(&folder.path).is_dir() {
+            return Err(format!("Not a directory: {}", folder.path));
+        }
+    }
+
+    settings::update(&app, |settings| settings.watch_folders = folders.clone())?;
+    apply(&app, folders)
+}
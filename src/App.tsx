@@ -1,4 +1,4 @@
-import { useState, useEffect, useRef } from 'react';
+import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 import { Github, Coffee, HelpCircle } from "lucide-react";
@@ -9,7 +9,7 @@ import Settings from './components/Settings';
 import { Progress } from './components/Progress';
 import HelpModal from './components/HelpModal';
 import CleanupModal from './components/CleanupModal';
-import { VideoFilter, VideoModifier, VideoOptions, FileStatus, LogPayload, ProcessingStats } from './types';
+import { VideoFilter, VideoModifier, VideoOptions, FileStatus, LogPayload, QueueEvent, ScheduleStatus, FfmpegInfo, AppSettings, ConfigWarning } from './types';
 import './App.css';
 
 function App() {
@@ -32,8 +32,10 @@ function App() {
   const [selectedFileLog, setSelectedFileLog] = useState<string | null>(null);
   const [showHelp, setShowHelp] = useState(false);
   const [showCleanup, setShowCleanup] = useState(false);
-
-  const abortRef = useRef(false);
+  const [scheduleStatus, setScheduleStatus] = useState<ScheduleStatus | null>(null);
+  const [ffmpegInfo, setFfmpegInfo] = useState<FfmpegInfo | null>(null);
+  const [configWarnings, setConfigWarnings] = useState<ConfigWarning[]>([]);
+  const [settingsLoaded, setSettingsLoaded] = useState(false);
 
   useEffect(() => {
     const unlisten = listen<LogPayload>('processing-log', (event) => {
@@ -43,14 +45,59 @@ function App() {
       });
     });
 
+    // The batch queue runs in the backend; follow its progress
+    const unlistenQueue = listen<QueueEvent>('queue-event', (event) => {
+      const { path, status, message, stats } = event.payload;
+      if (status === 'processing') {
+        setSelectedFileLog(path); // Auto-select log for current file
+      }
+      setFiles(prev => prev.map(f => {
+        if (f.path !== path) return f;
+        if (status === 'done' || status === 'skipped') {
+          return { ...f, status, processed: true, stats: stats ?? undefined };
+        }
+        return { ...f, status, error: status === 'error' ? (message ?? undefined) : f.error };
+      }));
+    });
+    const unlistenIdle = listen('queue-idle', () => setIsProcessing(false));
+    const unlistenSchedule = listen<ScheduleStatus>('schedule-status', (event) => setScheduleStatus(event.payload));
+    const unlistenFfmpeg = listen<FfmpegInfo>('ffmpeg-status', (event) => setFfmpegInfo(event.payload));
+    const unlistenConfig = listen<ConfigWarning>('config-warning', (event) => setConfigWarnings(prev => [...prev, event.payload]));
+
     invoke<VideoFilter[]>('get_filters').then(setFilters);
     invoke<VideoModifier[]>('get_modifiers').then(setModifiers);
+    invoke<ScheduleStatus>('get_schedule_status').then(setScheduleStatus);
+    invoke<FfmpegInfo>('get_ffmpeg_info').then(setFfmpegInfo);
+    // Warnings from startup were emitted before we were listening
+    invoke<ConfigWarning[]>('get_config_warnings').then(setConfigWarnings);
+    invoke<AppSettings>('load_settings')
+      .then((settings) => setOptions(settings.defaults))
+      .catch((err) => console.error('Failed to load settings:', err))
+      .finally(() => setSettingsLoaded(true));
 
     return () => {
       unlisten.then((f) => f());
+      unlistenQueue.then((f) => f());
+      unlistenIdle.then((f) => f());
+      unlistenSchedule.then((f) => f());
+      unlistenFfmpeg.then((f) => f());
+      unlistenConfig.then((f) => f());
     };
   }, []);
 
+  // Remember the options as the defaults for the next session
+  useEffect(() => {
+    if (!settingsLoaded) return;
+    const timer = setTimeout(async () => {
+      try {
+        await invoke('save_default_options', { defaults: options });
+      } catch (err) {
+        console.error('Failed to save settings:', err);
+      }
+    }, 500);
+    return () => clearTimeout(timer);
+  }, [options, settingsLoaded]);
+
   const handleFileSelect = async (newFiles: FileStatus[]) => {
     // Check status for each file
     const checkedFiles = await Promise.all(newFiles.map(async (file) => {
@@ -109,64 +156,32 @@ function App() {
   };
 
   const startProcessing = async () => {
-    if (files.length === 0) return;
+    const queued = files.filter(f => f.status !== 'done' && f.status !== 'skipped');
+    if (queued.length === 0) return;
 
     setIsProcessing(true);
     setIsAborted(false);
-    abortRef.current = false;
-
-    const currentOptions: VideoOptions = {
-      ...options,
-      modifiers: options.modifiers
-    };
 
     // Reset status for pending files
     setFiles(prev => prev.map(f =>
       f.status === 'error' || f.status === 'done' || f.status === 'aborted' ? { ...f, status: 'pending' } : f
     ));
 
-    for (const file of files) {
-      if (abortRef.current) {
-        break;
-      }
-      if (file.status === 'done' || file.status === 'skipped') {
-        continue;
-      }
-
-      setFiles(prev => prev.map(f => f.path === file.path ? { ...f, status: 'processing' } : f));
-      setSelectedFileLog(file.path); // Auto-select log for current file
-
-      try {
-        const stats = await invoke<ProcessingStats>('process_video', {
-          inputPath: file.path,
-          options: currentOptions
-        });
-
-        if (abortRef.current) {
-          setFiles(prev => prev.map(f => f.path === file.path ? { ...f, status: 'aborted' } : f));
-          break;
-        }
-
-        setFiles(prev => prev.map(f => f.path === file.path ? { ...f, status: 'done', processed: true, stats } : f));
-      } catch (err) {
-        console.error('Processing failed:', err);
-
-        if (abortRef.current) {
-          setFiles(prev => prev.map(f => f.path === file.path ? { ...f, status: 'aborted' } : f));
-          break;
-        } else {
-          setFiles(prev => prev.map(f => f.path === file.path ? { ...f, status: 'error', error: String(err) } : f));
-        }
-      }
+    try {
+      await invoke('enqueue_files', {
+        paths: queued.map(f => f.path),
+        options
+      });
+    } catch (err) {
+      console.error('Failed to queue files:', err);
+      setIsProcessing(false);
     }
-
-    setIsProcessing(false);
   };
 
   const handleAbort = async () => {
-    abortRef.current = true;
     setIsAborted(true);
     try {
+      await invoke('clear_queue');
       await invoke('cancel_processing');
     } catch (err) {
       console.error('Failed to send abort signal:', err);
@@ -221,6 +236,7 @@ function App() {
   };
 
   const hasProcessedFiles = files.some(f => f.status === 'done' && f.stats);
+  const missingTools = ffmpegInfo ? [ffmpegInfo.ffmpeg, ffmpegInfo.ffprobe].filter(t => !t.found) : [];
 
   return (
     <div className="container">
@@ -242,6 +258,18 @@ function App() {
         </div>
       </header>
 
+      {missingTools.length > 0 && (
+        <div className="tool-error">
+          {missingTools.map(t => <div key={t.path}>{t.error ?? `${t.path} is not usable`}</div>)}
+        </div>
+      )}
+
+      {configWarnings.length > 0 && (
+        <div className="tool-error">
+          {configWarnings.map((w, i) => <div key={i}>{w.source}: {w.message}</div>)}
+        </div>
+      )}
+
       <main>
         <div className="left-panel">
           <FileSelector
@@ -261,7 +289,7 @@ function App() {
               <button
                 className="primary-btn"
                 onClick={startProcessing}
-                disabled={files.length === 0}
+                disabled={files.length === 0 || missingTools.length > 0}
               >
                 Start Processing
               </button>
@@ -292,7 +320,7 @@ function App() {
           </div>
           <Progress
             logs={selectedFileLog ? (logs[selectedFileLog] || []) : []}
-            status={selectedFileLog ? 'Viewing Log' : (isProcessing ? 'Processing' : 'Idle')}
+            status={selectedFileLog ? 'Viewing Log' : (isProcessing ? (scheduleStatus && !scheduleStatus.open ? `Waiting for schedule: ${scheduleStatus.reason}` : 'Processing') : 'Idle')}
             fileName={selectedFileLog ? files.find(f => f.path === selectedFileLog)?.name : undefined}
             onCloseLog={() => setSelectedFileLog(null)}
           />
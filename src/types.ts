@@ -19,14 +19,31 @@ export interface VideoOptions {
   preset: string;
   hwaccel: string;
   tag_original: boolean;
+  tag_mode?: TagMode;
   stabilize: boolean;
+  output_dir?: string | null;
+  mirror_root?: string | null;
+  filename_template?: string | null;
+  on_collision?: CollisionPolicy;
+  replace_original?: boolean;
+  backup_dir?: string | null;
+  scratch_dir?: string | null;
+  segmented?: boolean;
+  segment_secs?: number | null;
+  segment_workers?: number | null;
 }
 
+export type CollisionPolicy = 'overwrite' | 'skip' | 'number';
+
+export type TagMode = 'file' | 'sidecar' | 'database';
+
 export interface ProcessingStats {
   duration_secs: number;
   original_size: number;
   new_size: number;
   output_path: string;
+  skipped: boolean;
+  metadata_warnings?: string[];
 }
 
 export interface FileStatus {
@@ -42,3 +59,158 @@ export interface LogPayload {
   path: string;
   message: string;
 }
+
+export interface PreviewSample {
+  sample_path: string;
+  sample_size: number;
+  sample_duration_secs: number;
+  source_duration_secs: number;
+  projected_size: number;
+  original_frame: string;
+  encoded_frame: string;
+}
+
+export interface ScanEntry {
+  path: string;
+  name: string;
+  status: 'pending' | 'skipped' | 'error';
+  error?: string;
+}
+
+export interface ScanBatch {
+  root: string;
+  entries: ScanEntry[];
+}
+
+export interface ScanSummary {
+  root: string;
+  found: number;
+  pending: number;
+  skipped: number;
+  errors: number;
+  skipped_outputs: number;
+}
+
+export interface WatchFolder {
+  path: string;
+  options: VideoOptions;
+  recursive: boolean;
+  enabled: boolean;
+  output_dir?: string | null;
+  archive_dir?: string | null;
+}
+
+export interface ResourceLimits {
+  nice?: number | null;
+  io_class?: number | null;
+  io_level?: number | null;
+  threads?: number | null;
+  cpu_quota?: number | null;
+}
+
+export interface ToolPaths {
+  ffmpeg?: string | null;
+  ffprobe?: string | null;
+}
+
+export interface ToolInfo {
+  path: string;
+  found: boolean;
+  version?: string | null;
+  configuration: string[];
+  filters: string[];
+  error?: string | null;
+}
+
+export interface FfmpegInfo {
+  ffmpeg: ToolInfo;
+  ffprobe: ToolInfo;
+}
+
+export interface ConfigWarning {
+  source: string;
+  message: string;
+}
+
+export interface Preset {
+  name: string;
+  options: VideoOptions;
+}
+
+export interface AppSettings {
+  version: number;
+  defaults: VideoOptions;
+  ffmpeg: ToolPaths;
+  presets: Preset[];
+  limits: ResourceLimits;
+  schedule: Schedule;
+  watch_folders: WatchFolder[];
+}
+
+export type ImportMode = 'merge' | 'replace';
+
+export interface ImportConflict {
+  kind: 'filter' | 'modifier' | 'preset' | 'watch_folder';
+  short_name: string;
+}
+
+export interface ImportReport {
+  filters_added: string[];
+  modifiers_added: string[];
+  presets_added: string[];
+  watch_folders_added: string[];
+  sections_replaced: string[];
+  conflicts: ImportConflict[];
+  warnings: string[];
+  applied: boolean;
+}
+
+export interface JobStatusEvent {
+  path: string;
+  status: 'running' | 'paused' | 'cancelling';
+}
+
+export interface TimeWindow {
+  start: string;
+  end: string;
+}
+
+export interface Schedule {
+  enabled: boolean;
+  windows: TimeWindow[];
+  require_idle: boolean;
+  idle_cpu_percent?: number | null;
+  require_ac_power: boolean;
+  on_close: 'pause' | 'finish_file';
+}
+
+export interface ScheduleStatus {
+  open: boolean;
+  reason?: string | null;
+}
+
+export interface QueueEvent {
+  path: string;
+  status: 'processing' | 'done' | 'skipped' | 'error' | 'aborted';
+  message?: string | null;
+  stats?: ProcessingStats | null;
+}
+
+export interface WatchEvent {
+  folder: string;
+  path: string;
+  status: 'queued' | 'processing' | 'done' | 'error' | 'skipped';
+  message?: string | null;
+}
+
+export interface JournalEntry {
+  id: string;
+  timestamp: string;
+  operation: 'delete' | 'move' | 'replace' | 'tag';
+  original_path: string;
+  new_path?: string | null;
+  backup_path?: string | null;
+  trashed: boolean;
+  hash?: string | null;
+  undone: boolean;
+}